@@ -1,3 +1,3 @@
-mod asm;
+mod gen;
 
-pub use asm::{generate, Error};
+pub use gen::{generate, Error};
@@ -0,0 +1,300 @@
+use crate::{
+    ast::{Kind as NodeKind, Node, Program, Switch},
+    diagnostic::Diagnostic,
+};
+use std::{fmt, io::{self, Write}, result::Result as StdResult};
+
+#[derive(Debug)]
+pub enum Error {
+   Write(io::Error),
+    UnexpectedNode(NodeKind),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+       match self {
+           Error::Write(e) => write!(f,"{}", e),
+           Error::UnexpectedNode(kind) => write!(f, "unexpected node {:?}", kind),
+       }
+    }
+}
+
+impl Error {
+    // generated code has no location, the nodes do not keep their tokens.
+    pub(crate) fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.to_string())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Write(e)
+    }
+}
+
+type Result<T> = StdResult<T, Error>;
+
+pub fn generate<W: Write>(w: &mut W, program: Program) -> StdResult<(), crate::Error> {
+    Generator::new(w)
+        .generate(program)
+        .map_err(crate::Error::from)
+}
+
+struct Generator<'a, W> {
+    w: &'a mut W,
+    label: usize,           // sequence for unique jump labels
+    breaks: Vec<String>,    // break targets, innermost last
+    continues: Vec<String>, // continue targets, innermost last
+    switches: Vec<usize>,   // labels of enclosing switch statements
+}
+
+impl<'a, W: Write> Generator<'a, W> {
+    fn new(w: &'a mut W) -> Self {
+        Self {
+            w,
+            label: 0,
+            breaks: Vec::new(),
+            continues: Vec::new(),
+            switches: Vec::new(),
+        }
+    }
+
+    fn generate(&mut self, program: Program) -> Result<()> {
+        self.pre_gen()?;
+        self.prologue(&program)?;
+        self.main_gen(program)?;
+        self.epilogue()
+    }
+
+    fn pre_gen(&mut self) -> Result<()> {
+        write!(
+            self.w,
+            ".intel_syntax noprefix\n\
+             .global main\n\
+             main:\n",
+        )?;
+        Ok(())
+    }
+
+    fn prologue(&mut self, program: &Program) -> Result<()> {
+        let size = program.iter().map(stack_size).max().unwrap_or(0);
+        writeln!(self.w, "  push rbp")?;
+        writeln!(self.w, "  mov rbp, rsp")?;
+        writeln!(self.w, "  sub rsp, {}", size.div_ceil(16) * 16)?;
+        Ok(())
+    }
+
+    fn main_gen(&mut self, program: Program) -> Result<()> {
+        for node in &program {
+            self.gen_stmt(node)?;
+        }
+        Ok(())
+    }
+
+    fn gen_stmt(&mut self, node: &Node) -> Result<()> {
+        match node.kind {
+            NodeKind::Block => {
+                for stmt in &node.body {
+                    self.gen_stmt(stmt)?;
+                }
+            }
+            NodeKind::Switch(ref switch) => self.gen_switch(node, switch)?,
+            NodeKind::Case(n) => {
+                let switch = *self.switches.last().unwrap();
+                writeln!(self.w, ".Lcase{}_{}:", switch, n)?;
+                self.gen_stmt(node.lhs.as_ref().unwrap())?;
+            }
+            NodeKind::Default => {
+                let switch = *self.switches.last().unwrap();
+                writeln!(self.w, ".Ldefault{}:", switch)?;
+                self.gen_stmt(node.lhs.as_ref().unwrap())?;
+            }
+            NodeKind::Break => {
+                let target = self.breaks.last().unwrap();
+                writeln!(self.w, "  jmp {}", target)?;
+            }
+            NodeKind::Continue => {
+                let target = self.continues.last().unwrap();
+                writeln!(self.w, "  jmp {}", target)?;
+            }
+            NodeKind::Label(ref name) => {
+                writeln!(self.w, "{}:", user_label(name))?;
+                self.gen_stmt(node.lhs.as_ref().unwrap())?;
+            }
+            NodeKind::Goto(ref name) => writeln!(self.w, "  jmp {}", user_label(name))?,
+            _ => {
+                self.gen(node)?;
+                writeln!(self.w, "  pop rax")?;
+            }
+        }
+        Ok(())
+    }
+
+    fn gen_switch(&mut self, node: &Node, switch: &Switch) -> Result<()> {
+        let label = self.next_label();
+        self.gen(node.cond.as_ref().unwrap())?;
+        writeln!(self.w, "  pop rax")?;
+        for n in &switch.cases {
+            writeln!(self.w, "  mov rdi, {}", n)?;
+            writeln!(self.w, "  cmp rax, rdi")?;
+            writeln!(self.w, "  je .Lcase{}_{}", label, n)?;
+        }
+        if switch.has_default {
+            writeln!(self.w, "  jmp .Ldefault{}", label)?;
+        } else {
+            writeln!(self.w, "  jmp .Lbreak{}", label)?;
+        }
+
+        self.breaks.push(format!(".Lbreak{}", label));
+        self.switches.push(label);
+        self.gen_stmt(node.lhs.as_ref().unwrap())?;
+        self.switches.pop();
+        self.breaks.pop();
+
+        writeln!(self.w, ".Lbreak{}:", label)?;
+        Ok(())
+    }
+
+    fn gen_local_var(&mut self, node: &Node) -> Result<()> {
+        if let NodeKind::LocalVar(lv) = &node.kind {
+            writeln!(self.w, "  mov rax, rbp")?;
+            writeln!(self.w, "  sub rax, {}", lv.offset)?;
+            writeln!(self.w, "  push rax")?;
+            Ok(())
+        } else {
+            Err(Error::UnexpectedNode(node.kind.clone()))
+        }
+    }
+
+    fn gen(&mut self, node: &Node) -> Result<()> {
+        match node.kind {
+            NodeKind::Number(n) => {
+                // push takes an immediate of 32 bits, sign extended to 64.
+                let n = n as i64;
+                if (i32::MIN as i64..=i32::MAX as i64).contains(&n) {
+                    writeln!(self.w, "  push {}", n)?;
+                } else {
+                    writeln!(self.w, "  mov rax, {}", n)?;
+                    writeln!(self.w, "  push rax")?;
+                }
+                return Ok(());
+            },
+            NodeKind::LocalVar(_) => {
+                self.gen_local_var(node)?;
+                writeln!(self.w, "  pop rax")?;
+                writeln!(self.w, "  mov rax, [rax]")?;
+                writeln!(self.w, "  push rax")?;
+                return Ok(());
+            },
+            NodeKind::Assign => {
+                self.gen_local_var(node.lhs.as_ref().unwrap())?;
+                self.gen(node.rhs.as_ref().unwrap())?;
+                writeln!(self.w, "  pop rdi")?;
+                writeln!(self.w, "  pop rax")?;
+                writeln!(self.w, "  mov [rax], rdi")?;
+                writeln!(self.w, "  push rdi")?;
+                return Ok(());
+            },
+            NodeKind::Cond => {
+                let label = self.next_label();
+                self.gen(node.cond.as_ref().unwrap())?;
+                writeln!(self.w, "  pop rax")?;
+                writeln!(self.w, "  cmp rax, 0")?;
+                writeln!(self.w, "  je .Lelse{}", label)?;
+                self.gen(node.lhs.as_ref().unwrap())?;
+                writeln!(self.w, "  jmp .Lend{}", label)?;
+                writeln!(self.w, ".Lelse{}:", label)?;
+                self.gen(node.rhs.as_ref().unwrap())?;
+                writeln!(self.w, ".Lend{}:", label)?;
+                return Ok(());
+            },
+            NodeKind::Comma => {
+                self.gen(node.lhs.as_ref().unwrap())?;
+                writeln!(self.w, "  pop rax")?;
+                self.gen(node.rhs.as_ref().unwrap())?;
+                return Ok(());
+            },
+            _ => (),
+        }
+
+        self.gen(node.lhs.as_ref().unwrap())?;
+        self.gen(node.rhs.as_ref().unwrap())?;
+
+        writeln!(self.w, "  pop rdi")?;
+        writeln!(self.w, "  pop rax")?;
+
+        match node.kind {
+            NodeKind::Add => writeln!(self.w, "  add rax, rdi")?,
+            NodeKind::Sub => writeln!(self.w, "  sub rax, rdi")?,
+            NodeKind::Mul => writeln!(self.w, "  imul rax, rdi")?,
+            NodeKind::Div => {
+                writeln!(self.w, "  cqo")?;
+                writeln!(self.w, "  idiv rdi")?;
+            }
+            NodeKind::Eq => write!(
+                self.w,
+                "  cmp rax, rdi\n  \
+                 sete al\n  \
+                 movzb rax, al\n",
+            )?,
+            NodeKind::Ne => write!(
+                self.w,
+                "  cmp rax, rdi\n  \
+                 setne al\n  \
+                 movzb rax, al\n",
+            )?,
+            NodeKind::Lt => write!(
+                self.w,
+                "  cmp rax, rdi\n  \
+                 setl al\n  \
+                 movzb rax, al\n",
+            )?,
+            NodeKind::Le => write!(
+                self.w,
+                "  cmp rax, rdi\n  \
+                 setle al\n  \
+                 movzb rax, al\n",
+            )?,
+            NodeKind::Number(_) => unreachable!(),
+            _ => unimplemented!()
+        }
+
+        writeln!(self.w, "  push rax")?;
+
+        Ok(())
+    }
+
+    fn epilogue(&mut self) -> Result<()> {
+        writeln!(self.w, "  mov rsp, rbp")?;
+        writeln!(self.w, "  pop rbp")?;
+        writeln!(self.w, "  ret")?;
+        // the stack need not be executable.
+        writeln!(self.w, ".section .note.GNU-stack,\"\",@progbits")?;
+        Ok(())
+    }
+
+    fn next_label(&mut self) -> usize {
+        let label = self.label;
+        self.label += 1;
+        label
+    }
+}
+
+// bytes of the stack frame used by the local variables in node.
+fn stack_size(node: &Node) -> u64 {
+    let own = match node.kind {
+        NodeKind::LocalVar(ref lv) => lv.offset,
+        _ => 0,
+    };
+    [&node.lhs, &node.rhs, &node.cond]
+        .iter()
+        .filter_map(|link| link.as_deref())
+        .chain(node.body.iter())
+        .map(stack_size)
+        .fold(own, u64::max)
+}
+
+// user defined labels are scoped to the function, main is the only one for now.
+fn user_label(name: &str) -> String {
+    format!(".L.main.{}", name)
+}
@@ -1,5 +1,5 @@
 pub mod node;
 pub mod parser;
 
-pub use node::{Kind, Node,Program};
-pub use parser::{parse, Error};
+pub use node::{tree, Kind, Node, Program, Switch};
+pub use parser::{parse, parse_reporting, Error, Warning};
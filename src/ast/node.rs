@@ -11,12 +11,29 @@ pub enum Kind {
     Lt,
     Le,
     Assign,
+    Cond,  // cond ? lhs : rhs
+    Comma, // lhs, rhs
     LocalVar(LocalVar),
     Number(u64),
+    Block,          // { body }
+    Switch(Switch), // switch (cond) lhs
+    Case(u64),      // case n: lhs
+    Default,        // default: lhs
+    Break,
+    Continue,
+    Label(String), // name: lhs
+    Goto(String),
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Switch {
+    pub cases: Vec<u64>, // case values in source order
+    pub has_default: bool,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct LocalVar {
+    pub name: String,
     pub offset: u64, // offset from base pointer
 }
 
@@ -27,11 +44,19 @@ pub struct Node {
     pub kind: Kind,
     pub lhs: Link,
     pub rhs: Link,
+    pub cond: Link,
+    pub body: Vec<Node>,
 }
 
 impl Node {
     pub fn new(kind: Kind, lhs: Link, rhs: Link) -> Node {
-        Self { kind, lhs, rhs }
+        Self {
+            kind,
+            lhs,
+            rhs,
+            cond: None,
+            body: Vec::new(),
+        }
     }
     pub fn with(kind: Kind, lhs: Node, rhs: Node) -> Node {
         Node::new(kind, Node::link(lhs), Node::link(rhs))
@@ -45,14 +70,69 @@ impl Node {
             _ => panic!("operation kind required. got {:?}", kind),
         }
     }
+    pub fn cond(cond: Node, then: Node, els: Node) -> Node {
+        Self {
+            cond: Node::link(cond),
+            ..Node::with(Kind::Cond, then, els)
+        }
+    }
+    pub fn block(body: Vec<Node>) -> Node {
+        Self {
+            body,
+            ..Node::new(Kind::Block, None, None)
+        }
+    }
+    pub fn switch(switch: Switch, cond: Node, body: Node) -> Node {
+        Self {
+            cond: Node::link(cond),
+            ..Node::new(Kind::Switch(switch), Node::link(body), None)
+        }
+    }
     pub fn link(node: Node) -> Link {
         Some(Box::new(node))
     }
     pub fn number(n: u64) -> Node {
         Node::new(Kind::Number(n), None, None)
     }
-    pub fn local_var(offset: u64) -> Node {
-        Node::new(Kind::LocalVar(LocalVar{offset}), None, None)
+    pub fn local_var(name: &str, offset: u64) -> Node {
+        Node::new(Kind::LocalVar(LocalVar{name: name.to_owned(), offset}), None, None)
     }
 }
 
+// program as an indented tree, each node on its own line above its children.
+pub fn tree(program: &Program) -> String {
+    let mut s = String::new();
+    for node in program {
+        node.tree(0, &mut s);
+    }
+    s
+}
+
+impl Node {
+    fn tree(&self, depth: usize, s: &mut String) {
+        use Kind::*;
+        let label = match self.kind {
+            LocalVar(ref var) => format!("LocalVar {} [rbp-{}]", var.name, var.offset),
+            Number(n) => format!("Number {}", n),
+            Switch(ref switch) => format!(
+                "Switch cases={:?}{}",
+                switch.cases,
+                if switch.has_default { " default" } else { "" }
+            ),
+            Case(n) => format!("Case {}", n),
+            Label(ref name) => format!("Label {}", name),
+            Goto(ref name) => format!("Goto {}", name),
+            ref kind => format!("{:?}", kind),
+        };
+        s.push_str(&"  ".repeat(depth));
+        s.push_str(&label);
+        s.push('\n');
+        let children = self.cond.iter().chain(self.lhs.iter()).chain(self.rhs.iter());
+        for child in children {
+            child.tree(depth + 1, s);
+        }
+        for stmt in &self.body {
+            stmt.tree(depth + 1, s);
+        }
+    }
+}
@@ -1,86 +1,536 @@
 use crate::{
-    ast::node::{Kind, Node, Program},
+    ast::node::{Kind, Node, Program, Switch},
+    diagnostic::{self, Diagnostic, Diagnostics},
     lex::{Stream, Token, TokenKind, Ident},
 };
-use std::{error::Error as StdError, fmt, iter::Peekable, result::Result as StdResult};
+use std::{collections::VecDeque, error::Error as StdError, fmt, result::Result as StdResult};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Error {
-    UnexpectedToken(Token),
-    Eof,
+    UnexpectedToken(Token, &'static str), // found token and what was expected instead
+    StrayBreak(Token),     // break outside of a loop or switch
+    StrayContinue(Token),  // continue outside of a loop
+    StrayCase(Token),      // case or default outside of a switch
+    DuplicateCase(Token),  // case value already used in the same switch
+    DuplicateDefault(Token),
+    DuplicateLabel(Token, Token), // the label and its first definition
+    UndefinedLabel(Token), // goto target that is never defined
+    Eof,                   // stream ended without the Eof sentinel
 }
 
 impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "parser error")
+        use Error::*;
+        match self {
+            UnexpectedToken(t, expected) => write!(f, "expected {}, found {}", expected, found(t)),
+            StrayBreak(_) => write!(f, "break statement not within loop or switch"),
+            StrayContinue(_) => write!(f, "continue statement not within a loop"),
+            StrayCase(t) => write!(f, "'{}' label not within a switch statement", t.value),
+            DuplicateCase(t) => write!(f, "duplicate case value '{}'", t.value),
+            DuplicateDefault(_) => write!(f, "multiple default labels in one switch"),
+            DuplicateLabel(t, _) => write!(f, "duplicate label '{}'", t.value),
+            UndefinedLabel(t) => write!(f, "label '{}' used but not defined", t.value),
+            Eof => write!(f, "unexpected end of input"),
+        }
+    }
+}
+
+// the found token in "expected ..., found ..." messages.
+fn found(token: &Token) -> String {
+    match token.value {
+        TokenKind::Eof | TokenKind::Str(_) => token.value.to_string(),
+        ref kind => format!("'{}'", kind),
     }
 }
 
 impl StdError for Error {}
 
+impl Error {
+    // the token the error is about, for its location.
+    pub(crate) fn token(&self) -> Option<&Token> {
+        use Error::*;
+        match self {
+            UnexpectedToken(t, _) | StrayBreak(t) | StrayContinue(t) | StrayCase(t)
+            | DuplicateCase(t) | DuplicateDefault(t) | DuplicateLabel(t, _) | UndefinedLabel(t) => {
+                Some(t)
+            }
+            Eof => None,
+        }
+    }
+
+    pub(crate) fn diagnostic(&self) -> Diagnostic {
+        let d = Diagnostic::error(self.to_string());
+        let d = match self.token() {
+            Some(t) => d.at_token(t),
+            None => d,
+        };
+        match self {
+            Error::DuplicateLabel(_, first) => {
+                d.label(diagnostic::site(first), "previous definition is here".to_owned())
+            }
+            _ => d,
+        }
+    }
+}
+
+// something legal but likely a mistake, found in a program parsed without errors. each
+// kind is reported only when its -W flag is on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    UnusedVariable(Token),        // assigned but never read, at its first assignment
+    UnusedLabel(Token),           // defined but never a goto target
+    Uninitialized(Token),         // read before anything is assigned to it
+    SelfComparison(Token, bool),  // operator comparing a variable with itself and its result
+    ConstantComparison(Token, bool), // operator comparing two constants and its result
+    UnreachableCode(Token),       // first statement after a jump
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Warning::*;
+        match self {
+            UnusedVariable(t) => write!(f, "variable '{}' set but not used", t.value),
+            UnusedLabel(t) => write!(f, "label '{}' defined but not used", t.value),
+            Uninitialized(t) => write!(f, "'{}' is used uninitialized", t.value),
+            SelfComparison(_, result) => write!(f, "self-comparison always evaluates to {}", result),
+            ConstantComparison(_, result) => {
+                write!(f, "comparison of constants always evaluates to {}", result)
+            }
+            UnreachableCode(_) => write!(f, "code will never be executed"),
+        }
+    }
+}
+
+impl Warning {
+    // the flag name, -Wname turns it on and -Wno-name off.
+    pub fn name(&self) -> &'static str {
+        use Warning::*;
+        match self {
+            UnusedVariable(_) => "unused-variable",
+            UnusedLabel(_) => "unused-label",
+            Uninitialized(_) => "uninitialized",
+            SelfComparison(..) | ConstantComparison(..) => "tautological-compare",
+            UnreachableCode(_) => "unreachable-code",
+        }
+    }
+
+    pub(crate) fn diagnostic(&self) -> Diagnostic {
+        use Warning::*;
+        let token = match self {
+            UnusedVariable(t) | UnusedLabel(t) | Uninitialized(t) | SelfComparison(t, _)
+            | ConstantComparison(t, _) | UnreachableCode(t) => t,
+        };
+        Diagnostic::warning(self.to_string())
+            .at_token(token)
+            .flag(self.name())
+    }
+}
+
 type Result<T> = StdResult<T, Error>;
 
 pub fn parse(stream: Stream) -> StdResult<Program, crate::Error> {
-    Parser::new(stream.into_iter().peekable())
-        .program()
-        .map_err(|e| crate::Error::from(e))
+    let mut error = None;
+    let program = Parser::new(stream.into_iter(), &mut |e| {
+        error = Some(e);
+        false
+    })
+    .program();
+    match error {
+        Some(e) => Err(e.into()),
+        None => Ok(program),
+    }
+}
+
+// parses like parse, reporting each error to diagnostics and going on after it until the
+// error limit is reached. the program is incomplete if any error was reported. warnings are
+// given back for the caller to pick from, there are none after an error.
+pub fn parse_reporting(stream: Stream, diagnostics: &mut Diagnostics) -> (Program, Vec<Warning>) {
+    let mut report = |e: Error| {
+        diagnostics.report(e.diagnostic());
+        !diagnostics.limit_reached()
+    };
+    let mut parser = Parser::new(stream.into_iter(), &mut report);
+    let program = parser.program();
+    let warnings = if parser.failed { Vec::new() } else { parser.warnings };
+    (program, warnings)
 }
 
-struct Parser<Tokens> {
+struct Parser<'a, Tokens> {
     tokens: Tokens,
+    report: &'a mut dyn FnMut(Error) -> bool, // takes errors, tells whether to go on
+    failed: bool,                             // some error was reported
+    stopped: bool,                            // parsing is given up, no more errors are taken
+    lookahead: VecDeque<Token>, // tokens peeked but not consumed yet
+    switches: Vec<Switch>, // enclosing switch statements, innermost last
+    loops: usize,          // nesting depth of enclosing loops
+    labels: Vec<Token>,    // labels defined in the current function
+    gotos: Vec<Token>,     // goto targets referenced in the current function
+    locals: Vec<String>,   // local variables in order of first use
+    uses: Vec<Use>,        // reads and writes of local variables in evaluation order
+    warnings: Vec<Warning>,
+}
+
+// a local variable in an expression, read unless it is assigned to.
+struct Use {
+    token: Token,
+    write: bool,
 }
 /*
-expr       = equality
-equality   = relational ("==" relational | "!=" relational)*
-relational = add ("<" add | "<=" add | ">" add | ">=" add)*
-add        = mul ("+" mul | "-" mul)*
-mul        = unary ("*" unary | "/" unary)*
-unary      = ("+" | "-" )? primary
-primary    = num | "(" expr ")"
+program     = stmt*
+stmt        = block
+            | "switch" "(" expr ")" stmt
+            | "case" num ":" stmt
+            | "default" ":" stmt
+            | "break" ";"
+            | "continue" ";"
+            | "goto" ident ";"
+            | ident ":" stmt
+            | expr? ";"
+block       = "{" stmt* "}"
+expr        = assign ("," assign)*
+assign      = conditional ("=" assign)?
+conditional = equality ("?" expr ":" conditional)?
+equality    = relational ("==" relational | "!=" relational)*
+relational  = add ("<" add | "<=" add | ">" add | ">=" add)*
+add         = mul ("+" mul | "-" mul)*
+mul         = unary ("*" unary | "/" unary)*
+unary       = ("+" | "-" )? primary
+primary     = num | ident | "(" expr ")"
  */
 
-impl<Tokens> Parser<Peekable<Tokens>>
+impl<'a, Tokens> Parser<'a, Tokens>
 where
     Tokens: Iterator<Item = Token>,
 {
-    fn new(tokens: Peekable<Tokens>) -> Self {
-        Self { tokens }
+    fn new(tokens: Tokens, report: &'a mut dyn FnMut(Error) -> bool) -> Self {
+        Self {
+            tokens,
+            report,
+            failed: false,
+            stopped: false,
+            lookahead: VecDeque::new(),
+            switches: Vec::new(),
+            loops: 0,
+            labels: Vec::new(),
+            gotos: Vec::new(),
+            locals: Vec::new(),
+            uses: Vec::new(),
+            warnings: Vec::new(),
+        }
     }
 
     // program = stmt *
-    fn program(&mut self) -> Result<Program> {
+    fn program(&mut self) -> Program {
         let mut program = Program::new();
         while !self.is_eof() {
-            program.push(self.stmt()?);
+            let start = self.peek().cloned();
+            match self.stmt() {
+                Ok(node) => {
+                    self.check_reachable(&program, &node, start.clone());
+                    program.push(node);
+                }
+                Err(e) => {
+                    if self.recover(e).is_err() {
+                        return program;
+                    }
+                    // a '}' without its '{' is where the error was, there is no block to close.
+                    if self.peek().is_some_and(|t| t.is_kind(TokenKind::RBrace)) {
+                        self.next();
+                    }
+                }
+            }
+        }
+        // gotos to labels in skipped statements are not errors of their own.
+        if !self.failed {
+            for e in self.resolve_labels() {
+                if self.recover(e).is_err() {
+                    break;
+                }
+            }
+        }
+        if !self.failed {
+            self.check_labels();
+            self.check_variables();
+        }
+        program
+    }
+
+    // reports e and skips to where parsing can go on, or gives e back if it cannot.
+    fn recover(&mut self, e: Error) -> Result<()> {
+        if self.stopped {
+            return Err(e);
         }
-        Ok(program)
+        self.failed = true;
+        if !(self.report)(e.clone()) || e == Error::Eof {
+            self.stopped = true;
+            return Err(e);
+        }
+        self.synchronize();
+        Ok(())
     }
 
-    // stmt = expr ";"
+    // skips past the next ';', or a whole block, in the statement an error is in. a '}' closing
+    // the enclosing block is left to it.
+    fn synchronize(&mut self) {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token.value {
+                TokenKind::Eof => return,
+                TokenKind::SemiColon if depth == 0 => {
+                    self.next();
+                    return;
+                }
+                TokenKind::LBrace => depth += 1,
+                TokenKind::RBrace if depth == 0 => return,
+                TokenKind::RBrace => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.next();
+                        return;
+                    }
+                }
+                _ => (),
+            }
+            self.next();
+        }
+    }
+
+    // stmt = block | switch | case | default | label
+    //      | "break" ";" | "continue" ";" | "goto" ident ";" | expr? ";"
     fn stmt(&mut self) -> Result<Node> {
-        let node = self.expr()?;
-        self.expect(TokenKind::SemiColon)?;
-        Ok(node)
+        let token = self.peek().cloned().ok_or(Error::Eof)?;
+        match token.value {
+            TokenKind::LBrace => self.block(),
+            TokenKind::Switch => self.switch(),
+            TokenKind::Case => self.case(),
+            TokenKind::Default => self.default(),
+            TokenKind::Break => {
+                self.next();
+                if self.switches.is_empty() && self.loops == 0 {
+                    return Err(Error::StrayBreak(token));
+                }
+                self.expect(TokenKind::SemiColon, "';' after 'break'")?;
+                Ok(Node::new(Kind::Break, None, None))
+            }
+            TokenKind::Continue => {
+                self.next();
+                if self.loops == 0 {
+                    return Err(Error::StrayContinue(token));
+                }
+                self.expect(TokenKind::SemiColon, "';' after 'continue'")?;
+                Ok(Node::new(Kind::Continue, None, None))
+            }
+            TokenKind::Goto => {
+                self.next();
+                let target = self.peek().cloned().ok_or(Error::Eof)?;
+                let ident = self.expect_ident("label name after 'goto'")?;
+                self.expect(TokenKind::SemiColon, "';' after 'goto' statement")?;
+                self.gotos.push(target);
+                Ok(Node::new(Kind::Goto(ident.name), None, None))
+            }
+            TokenKind::Ident(_) if self.peek_nth(1).is_some_and(|t| t.is_kind(TokenKind::Colon)) => {
+                self.label()
+            }
+            TokenKind::SemiColon => {
+                self.next();
+                Ok(Node::block(Vec::new()))
+            }
+            _ => {
+                let node = self.expr()?;
+                self.expect(TokenKind::SemiColon, "';' after expression")?;
+                Ok(node)
+            }
+        }
+    }
+
+    // block = "{" stmt* "}"
+    fn block(&mut self) -> Result<Node> {
+        self.expect(TokenKind::LBrace, "'{'")?;
+        let mut body = Vec::new();
+        while !self.consume(TokenKind::RBrace)? {
+            if self.is_eof() {
+                return Err(self.unexpected("'}' at end of block"));
+            }
+            let start = self.peek().cloned();
+            match self.stmt() {
+                Ok(node) => {
+                    self.check_reachable(&body, &node, start);
+                    body.push(node);
+                }
+                Err(e) => self.recover(e)?,
+            }
+        }
+        Ok(Node::block(body))
     }
 
-    // expr = assign
+    // switch = "switch" "(" expr ")" stmt
+    fn switch(&mut self) -> Result<Node> {
+        self.expect(TokenKind::Switch, "'switch'")?;
+        self.expect(TokenKind::LParen, "'(' after 'switch'")?;
+        let cond = self.expr()?;
+        self.expect(TokenKind::RParen, "')' after switch condition")?;
+
+        self.switches.push(Switch::default());
+        let body = self.stmt();
+        let switch = self.switches.pop().unwrap();
+        Ok(Node::switch(switch, cond, body?))
+    }
+
+    // case = "case" num ":" stmt
+    fn case(&mut self) -> Result<Node> {
+        let token = self.next().ok_or(Error::Eof)?;
+        let number = self.peek().cloned().ok_or(Error::Eof)?;
+        let n = self.expect_number("number after 'case'")?;
+        self.expect(TokenKind::Colon, "':' after case value")?;
+
+        let switch = self.switches.last_mut().ok_or(Error::StrayCase(token))?;
+        if switch.cases.contains(&n) {
+            return Err(Error::DuplicateCase(number));
+        }
+        switch.cases.push(n);
+        Ok(Node::new(Kind::Case(n), Node::link(self.stmt()?), None))
+    }
+
+    // default = "default" ":" stmt
+    fn default(&mut self) -> Result<Node> {
+        let token = self.next().ok_or(Error::Eof)?;
+        self.expect(TokenKind::Colon, "':' after 'default'")?;
+
+        let switch = self.switches.last_mut().ok_or_else(|| Error::StrayCase(token.clone()))?;
+        if switch.has_default {
+            return Err(Error::DuplicateDefault(token));
+        }
+        switch.has_default = true;
+        Ok(Node::new(Kind::Default, Node::link(self.stmt()?), None))
+    }
+
+    // label = ident ":" stmt
+    fn label(&mut self) -> Result<Node> {
+        let token = self.peek().cloned().ok_or(Error::Eof)?;
+        let ident = self.expect_ident("label name")?;
+        self.expect(TokenKind::Colon, "':' after label name")?;
+
+        if let Some(first) = self.labels.iter().find(|label| label.value == token.value) {
+            return Err(Error::DuplicateLabel(token, first.clone()));
+        }
+        self.labels.push(token);
+        Ok(Node::new(Kind::Label(ident.name), Node::link(self.stmt()?), None))
+    }
+
+    // labels are function scoped, so gotos may refer to labels defined after them. each
+    // undefined label is an error once, at its first goto.
+    fn resolve_labels(&self) -> Vec<Error> {
+        let mut undefined: Vec<&Token> = Vec::new();
+        for goto in &self.gotos {
+            if !self.labels.iter().any(|label| label.value == goto.value)
+                && !undefined.iter().any(|t| t.value == goto.value)
+            {
+                undefined.push(goto);
+            }
+        }
+        undefined.into_iter().map(|t| Error::UndefinedLabel(t.clone())).collect()
+    }
+
+    // a label nothing jumps to is left over from code that is gone.
+    fn check_labels(&mut self) {
+        let gotos = &self.gotos;
+        let unused = self
+            .labels
+            .iter()
+            .filter(|label| !gotos.iter().any(|goto| goto.value == label.value))
+            .map(|label| Warning::UnusedLabel(label.clone()));
+        self.warnings.extend(unused);
+    }
+
+    // a variable is uninitialized if it is first read, and unused if it is never read.
+    // gotos are not followed, uses are taken in the order they are written.
+    fn check_variables(&mut self) {
+        for local in &self.locals {
+            let mut uses = self
+                .uses
+                .iter()
+                .filter(|u| matches!(u.token.value, TokenKind::Ident(ref ident) if ident.name == *local));
+            let first = match uses.next() {
+                Some(first) => first,
+                None => continue,
+            };
+            if !first.write {
+                self.warnings.push(Warning::Uninitialized(first.token.clone()));
+            } else if !uses.any(|u| !u.write) {
+                self.warnings.push(Warning::UnusedVariable(first.token.clone()));
+            }
+        }
+    }
+
+    // stmt starting at start is unreachable if the statement before it jumps away, unless
+    // it can be jumped to. only the first of such statements is reported.
+    fn check_reachable(&mut self, before: &[Node], stmt: &Node, start: Option<Token>) {
+        if !before.last().is_some_and(jumps) || is_target(stmt) {
+            return;
+        }
+        self.warnings.extend(start.map(Warning::UnreachableCode));
+    }
+
+    // comparing a variable with itself, or two constants, has the same result every time.
+    fn check_comparison(&mut self, node: &Node, op: Option<Token>) {
+        let (lhs, rhs, op) = match (&node.lhs, &node.rhs, op) {
+            (Some(lhs), Some(rhs), Some(op)) => (lhs, rhs, op),
+            _ => return,
+        };
+        match (&lhs.kind, &rhs.kind) {
+            (Kind::LocalVar(a), Kind::LocalVar(b)) if a == b => {
+                let result = matches!(node.kind, Kind::Eq | Kind::Le);
+                self.warnings.push(Warning::SelfComparison(op, result));
+            }
+            _ => {
+                let operands = (constant(lhs), constant(rhs));
+                if let ((Some(_), Some(_)), Some(result)) = (operands, constant(node)) {
+                    self.warnings.push(Warning::ConstantComparison(op, result != 0));
+                }
+            }
+        }
+    }
+
+    // expr = assign ("," assign)*
     fn expr(&mut self) -> Result<Node> {
-        self.assign()
+        let mut node = self.assign()?;
+        while self.consume(TokenKind::Comma)? {
+            node = Node::with(Kind::Comma, node, self.assign()?);
+        }
+        Ok(node)
     }
 
-    // assign = equality ( "=" assign )*
+    // assign = conditional ( "=" assign )*
     fn assign(&mut self) -> Result<Node> {
-        let mut node = self.equality()?;
+        let mut node = self.conditional()?;
         while self.consume(TokenKind::Assign)? {
+            // a variable assigned to is written after the value is read, it is not read.
+            let target = match node.kind {
+                Kind::LocalVar(_) => self.uses.pop(),
+                _ => None,
+            };
             node = Node::with(Kind::Assign, node, self.assign()?);
+            self.uses.extend(target.map(|target| Use { write: true, ..target }));
         }
         Ok(node)
     }
 
+    // conditional = equality ("?" expr ":" conditional)?
+    fn conditional(&mut self) -> Result<Node> {
+        let node = self.equality()?;
+        if !self.consume(TokenKind::Question)? {
+            return Ok(node);
+        }
+        let then = self.expr()?;
+        self.expect(TokenKind::Colon, "':' in conditional expression")?;
+        Ok(Node::cond(node, then, self.conditional()?))
+    }
+
     // equality = relational ("==" relational | "!=" relational)*
     fn equality(&mut self) -> Result<Node> {
         let mut node = self.relational()?;
         loop {
+            let op = self.peek().cloned();
             if self.consume(TokenKind::Eq)? {
                 node = Node::with(Kind::Eq, node, self.relational()?);
             } else if self.consume(TokenKind::Ne)? {
@@ -88,6 +538,7 @@ where
             } else {
                 return Ok(node);
             }
+            self.check_comparison(&node, op);
         }
     }
 
@@ -95,6 +546,7 @@ where
     fn relational(&mut self) -> Result<Node> {
         let mut node = self.add()?;
         loop {
+            let op = self.peek().cloned();
             if self.consume(TokenKind::Lt)? {
                 node = Node::with(Kind::Lt, node, self.add()?);
             } else if self.consume(TokenKind::Le)? {
@@ -106,6 +558,7 @@ where
             } else {
                 return Ok(node);
             }
+            self.check_comparison(&node, op);
         }
     }
 
@@ -142,7 +595,7 @@ where
         let node = if self.consume(TokenKind::Plus)? {
             self.primary()?
         } else if self.consume(TokenKind::Minus)? {
-            Node::ops(Kind::Sub, 0, self.expect_number()?)
+            Node::ops(Kind::Sub, 0, self.expect_number("number after unary '-'")?)
         } else {
             self.primary()?
         };
@@ -153,79 +606,151 @@ where
     fn primary(&mut self) -> Result<Node> {
         let node = if self.consume(TokenKind::LParen)? {
             let node = self.expr()?;
-            self.expect(TokenKind::RParen)?;
+            self.expect(TokenKind::RParen, "')' to close '('")?;
             node
         } else if self.is_ident() {
-            let ident = self.expect_ident()?;
-            let offset = ((ident.name.chars().next().unwrap() as u8) - b'a' + 1) * 8;
-            Node::local_var(offset.into())
+            let token = self.peek().cloned().ok_or(Error::Eof)?;
+            let ident = self.expect_ident("identifier")?;
+            self.uses.push(Use { token, write: false });
+            let offset = self.local_offset(ident.name.clone());
+            Node::local_var(&ident.name, offset)
         } else {
-            Node::number(self.expect_number()?)
+            Node::number(self.expect_number("expression")?)
         };
         Ok(node)
     }
 
+    // every local variable gets its own 8 byte slot below the base pointer.
+    fn local_offset(&mut self, name: String) -> u64 {
+        let index = match self.locals.iter().position(|local| *local == name) {
+            Some(index) => index,
+            None => {
+                self.locals.push(name);
+                self.locals.len() - 1
+            }
+        };
+        (index as u64 + 1) * 8
+    }
+
+    fn peek(&mut self) -> Option<&Token> {
+        self.peek_nth(0)
+    }
+
+    fn peek_nth(&mut self, n: usize) -> Option<&Token> {
+        while self.lookahead.len() <= n {
+            self.lookahead.push_back(self.tokens.next()?);
+        }
+        self.lookahead.get(n)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        self.lookahead.pop_front().or_else(|| self.tokens.next())
+    }
+
     fn consume(&mut self, kind: TokenKind) -> Result<bool> {
         // how can i make this code to a method chain :(
-        let peek = self.tokens.peek();
+        let peek = self.peek();
         if peek.is_none() {
             return Ok(false);
         }
         let peek = peek.unwrap();
         if peek.is_kind(kind) {
-            self.tokens.next();
+            self.next();
             Ok(true)
         } else {
             Ok(false)
         }
     }
 
-    fn expect(&mut self, kind: TokenKind) -> Result<()> {
-        self.tokens
-            .peek()
-            .ok_or(Error::Eof)
-            .and_then(|peek| {
-                if peek.is_kind(kind) {
-                    Ok(())
-                } else {
-                    Err(Error::UnexpectedToken(peek.clone()))
-                }
-            })
-            .map(|unit| {
-                self.tokens.next();
-                unit
-            })
+    // expected describes what should come next, like "';' after expression".
+    fn expect(&mut self, kind: TokenKind, expected: &'static str) -> Result<()> {
+        if !self.peek().is_some_and(|peek| peek.is_kind(kind)) {
+            return Err(self.unexpected(expected));
+        }
+        self.next();
+        Ok(())
     }
 
-    fn expect_number(&mut self) -> Result<u64> {
-        self.tokens
-            .peek()
-            .ok_or(Error::Eof)
-            .and_then(|peek| match peek.value {
-                TokenKind::Number(n) => Ok(n),
-                _ => Err(Error::UnexpectedToken(peek.clone())),
-            })
-            .map(|n| {
-                self.tokens.next();
-                n
-            })
+    fn expect_number(&mut self, expected: &'static str) -> Result<u64> {
+        match self.peek().map(|peek| &peek.value) {
+            Some(&TokenKind::Number(n)) => {
+                self.next();
+                Ok(n)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
     }
-    fn expect_ident(&mut self) -> Result<Ident> {
-        self.tokens.peek().ok_or(Error::Eof).and_then(|peek| match peek.value {
-            TokenKind::Ident(ref ident) => Ok(ident.clone()),
-            _ => Err(Error::UnexpectedToken(peek.clone())),
-        }).map(|ident| {
-            self.tokens.next();
-            ident
-        })
+    fn expect_ident(&mut self, expected: &'static str) -> Result<Ident> {
+        match self.peek().map(|peek| &peek.value) {
+            Some(TokenKind::Ident(ident)) => {
+                let ident = ident.clone();
+                self.next();
+                Ok(ident)
+            }
+            _ => Err(self.unexpected(expected)),
+        }
+    }
+    // the error for the next token when it is not the expected one.
+    fn unexpected(&mut self, expected: &'static str) -> Error {
+        match self.peek() {
+            Some(peek) => Error::UnexpectedToken(peek.clone(), expected),
+            None => Error::Eof,
+        }
     }
     fn is_ident(&mut self) -> bool {
-       self.tokens.peek().map_or(false, |peek| peek.is_ident())
+       self.peek().is_some_and(|peek| peek.is_ident())
     }
     fn is_eof(&mut self) -> bool {
-        self.tokens
-            .peek()
-            .map_or(true, |peek| peek.is_kind(TokenKind::Eof))
+        self.peek()
+            .is_none_or(|peek| peek.is_kind(TokenKind::Eof))
+    }
+}
+
+// whether control never goes on past node to the statement after it.
+fn jumps(node: &Node) -> bool {
+    match node.kind {
+        Kind::Break | Kind::Continue | Kind::Goto(_) => true,
+        Kind::Case(_) | Kind::Default | Kind::Label(_) => node.lhs.as_deref().is_some_and(jumps),
+        Kind::Block => node.body.last().is_some_and(jumps),
+        _ => false,
+    }
+}
+
+// whether control can enter node other than from the statement before it.
+fn is_target(node: &Node) -> bool {
+    match node.kind {
+        Kind::Case(_) | Kind::Default | Kind::Label(_) => true,
+        Kind::Block => node.body.first().is_some_and(is_target),
+        _ => false,
+    }
+}
+
+// the value of an expression without variables, computed in 64 bits like the generated code.
+fn constant(node: &Node) -> Option<i64> {
+    let operand = |link: &Option<Box<Node>>| link.as_deref().and_then(constant);
+    match node.kind {
+        Kind::Number(n) => Some(n as i64),
+        Kind::Cond => {
+            let cond = operand(&node.cond)?;
+            operand(if cond != 0 { &node.lhs } else { &node.rhs })
+        }
+        // the value of an assignment is the value assigned.
+        Kind::Comma | Kind::Assign => operand(&node.rhs),
+        Kind::Add | Kind::Sub | Kind::Mul | Kind::Div | Kind::Eq | Kind::Ne | Kind::Lt
+        | Kind::Le => {
+            let (a, b) = (operand(&node.lhs)?, operand(&node.rhs)?);
+            Some(match node.kind {
+                Kind::Add => a.wrapping_add(b),
+                Kind::Sub => a.wrapping_sub(b),
+                Kind::Mul => a.wrapping_mul(b),
+                Kind::Div => a.checked_div(b)?,
+                Kind::Eq => (a == b) as i64,
+                Kind::Ne => (a != b) as i64,
+                Kind::Lt => (a < b) as i64,
+                _ => (a <= b) as i64,
+            })
+        }
+        _ => None,
     }
 }
 
@@ -1,5 +1,8 @@
 use super::*;
-use crate::lex::token::Loc;
+use crate::{
+    ast::node::Switch,
+    lex::{token::Loc, tokenize},
+};
 
 #[test]
 fn parse_test() {
@@ -105,3 +108,382 @@ fn comparison_operator_test() {
     ];
     assert_eq!(parse(s).unwrap(), vec![Node::ops(Kind::Lt, 1, 3)]);
 }
+
+#[test]
+fn conditional_test() {
+    // '1 ? 2 : 3 ? 4 : 5'
+    let s = vec![
+        Token::number(1, Loc(0, 1)),
+        Token::question(Loc(2, 3)),
+        Token::number(2, Loc(4, 5)),
+        Token::colon(Loc(6, 7)),
+        Token::number(3, Loc(8, 9)),
+        Token::question(Loc(10, 11)),
+        Token::number(4, Loc(12, 13)),
+        Token::colon(Loc(14, 15)),
+        Token::number(5, Loc(16, 17)),
+        Token::semi_colon(Loc(17, 18)),
+    ];
+    assert_eq!(
+        parse(s).unwrap(),
+        vec![Node::cond(
+            Node::number(1),
+            Node::number(2),
+            Node::cond(Node::number(3), Node::number(4), Node::number(5)),
+        )],
+    );
+
+    // 'a = 1 ? 2 : 3'
+    let s = vec![
+        Token::ident("a", Loc(0, 1)),
+        Token::assign(Loc(2, 3)),
+        Token::number(1, Loc(4, 5)),
+        Token::question(Loc(6, 7)),
+        Token::number(2, Loc(8, 9)),
+        Token::colon(Loc(10, 11)),
+        Token::number(3, Loc(12, 13)),
+        Token::semi_colon(Loc(13, 14)),
+    ];
+    assert_eq!(
+        parse(s).unwrap(),
+        vec![Node::with(
+            Kind::Assign,
+            Node::local_var("a", 8),
+            Node::cond(Node::number(1), Node::number(2), Node::number(3)),
+        )],
+    );
+}
+
+#[test]
+fn comma_test() {
+    // 'a = 1, 2'
+    let s = vec![
+        Token::ident("a", Loc(0, 1)),
+        Token::assign(Loc(2, 3)),
+        Token::number(1, Loc(4, 5)),
+        Token::comma(Loc(5, 6)),
+        Token::number(2, Loc(7, 8)),
+        Token::semi_colon(Loc(8, 9)),
+    ];
+    assert_eq!(
+        parse(s).unwrap(),
+        vec![Node::with(
+            Kind::Comma,
+            Node::with(Kind::Assign, Node::local_var("a", 8), Node::number(1)),
+            Node::number(2),
+        )],
+    );
+}
+
+#[test]
+fn switch_test() {
+    // 'switch (a) { case 1: break; default: 2; }'
+    let s = vec![
+        Token::keyword(TokenKind::Switch, Loc(0, 6)),
+        Token::lparen(Loc(7, 8)),
+        Token::ident("a", Loc(8, 9)),
+        Token::rparen(Loc(9, 10)),
+        Token::lbrace(Loc(11, 12)),
+        Token::keyword(TokenKind::Case, Loc(13, 17)),
+        Token::number(1, Loc(18, 19)),
+        Token::colon(Loc(19, 20)),
+        Token::keyword(TokenKind::Break, Loc(21, 26)),
+        Token::semi_colon(Loc(26, 27)),
+        Token::keyword(TokenKind::Default, Loc(28, 35)),
+        Token::colon(Loc(35, 36)),
+        Token::number(2, Loc(37, 38)),
+        Token::semi_colon(Loc(38, 39)),
+        Token::rbrace(Loc(40, 41)),
+    ];
+    let switch = Switch {
+        cases: vec![1],
+        has_default: true,
+    };
+    assert_eq!(
+        parse(s).unwrap(),
+        vec![Node::switch(
+            switch,
+            Node::local_var("a", 8),
+            Node::block(vec![
+                Node::new(
+                    Kind::Case(1),
+                    Node::link(Node::new(Kind::Break, None, None)),
+                    None
+                ),
+                Node::new(Kind::Default, Node::link(Node::number(2)), None),
+            ]),
+        )],
+    );
+}
+
+#[test]
+fn switch_error_test() {
+    // 'break;'
+    let s = vec![
+        Token::keyword(TokenKind::Break, Loc(0, 5)),
+        Token::semi_colon(Loc(5, 6)),
+    ];
+    assert!(matches!(
+        parse(s),
+        Err(crate::Error::Parser(Error::StrayBreak(ref t))) if t.loc == Loc(0, 5)
+    ));
+
+    // 'switch (1) { continue; }'
+    let s = vec![
+        Token::keyword(TokenKind::Switch, Loc(0, 6)),
+        Token::lparen(Loc(7, 8)),
+        Token::number(1, Loc(8, 9)),
+        Token::rparen(Loc(9, 10)),
+        Token::lbrace(Loc(11, 12)),
+        Token::keyword(TokenKind::Continue, Loc(13, 21)),
+        Token::semi_colon(Loc(21, 22)),
+        Token::rbrace(Loc(23, 24)),
+    ];
+    assert!(matches!(
+        parse(s),
+        Err(crate::Error::Parser(Error::StrayContinue(ref t))) if t.loc == Loc(13, 21)
+    ));
+
+    // 'case 1: 1;'
+    let s = vec![
+        Token::keyword(TokenKind::Case, Loc(0, 4)),
+        Token::number(1, Loc(5, 6)),
+        Token::colon(Loc(6, 7)),
+        Token::number(1, Loc(8, 9)),
+        Token::semi_colon(Loc(9, 10)),
+    ];
+    assert!(matches!(
+        parse(s),
+        Err(crate::Error::Parser(Error::StrayCase(ref t))) if t.loc == Loc(0, 4)
+    ));
+
+    // 'switch (1) { case 1: case 1: 1; }'
+    let s = vec![
+        Token::keyword(TokenKind::Switch, Loc(0, 6)),
+        Token::lparen(Loc(7, 8)),
+        Token::number(1, Loc(8, 9)),
+        Token::rparen(Loc(9, 10)),
+        Token::lbrace(Loc(11, 12)),
+        Token::keyword(TokenKind::Case, Loc(13, 17)),
+        Token::number(1, Loc(18, 19)),
+        Token::colon(Loc(19, 20)),
+        Token::keyword(TokenKind::Case, Loc(21, 25)),
+        Token::number(1, Loc(26, 27)),
+        Token::colon(Loc(27, 28)),
+        Token::number(1, Loc(29, 30)),
+        Token::semi_colon(Loc(30, 31)),
+        Token::rbrace(Loc(32, 33)),
+    ];
+    assert!(matches!(
+        parse(s),
+        Err(crate::Error::Parser(Error::DuplicateCase(ref t))) if t.loc == Loc(26, 27)
+    ));
+}
+
+#[test]
+fn unexpected_token_test() {
+    let error = |input| match parse(tokenize(input).unwrap()) {
+        Err(crate::Error::Parser(e)) => e,
+        result => panic!("parser error expected. got {:?}", result),
+    };
+
+    let e = error("1 + 2)");
+    assert!(matches!(e, Error::UnexpectedToken(ref t, _) if t.loc == Loc(5, 6)));
+    assert_eq!(e.to_string(), "expected ';' after expression, found ')'");
+
+    let e = error("switch 1;");
+    assert_eq!(e.to_string(), "expected '(' after 'switch', found '1'");
+
+    let e = error("goto 1;");
+    assert_eq!(e.to_string(), "expected label name after 'goto', found '1'");
+
+    let e = error("1 ? 2;");
+    assert_eq!(e.to_string(), "expected ':' in conditional expression, found ';'");
+
+    let e = error("{ 1;");
+    assert!(matches!(e, Error::UnexpectedToken(ref t, _) if t.loc == Loc(4, 4)));
+    assert_eq!(e.to_string(), "expected '}' at end of block, found end of input");
+
+    // a stream without the Eof sentinel.
+    assert!(matches!(
+        parse(vec![Token::number(1, Loc(0, 1))]),
+        Err(crate::Error::Parser(Error::Eof))
+    ));
+}
+
+#[test]
+fn recovery_test() {
+    let recover = |input, max_errors| {
+        let mut diagnostics = Diagnostics::new(max_errors);
+        let (program, _) = parse_reporting(tokenize(input).unwrap(), &mut diagnostics);
+        let errors: Vec<String> = diagnostics.iter().map(|d| d.message.clone()).collect();
+        (program, errors)
+    };
+
+    // each statement with an error is skipped up to its ';'.
+    let (program, errors) = recover("1 + ;\n2 3;\n(4;\n5;", 0);
+    assert_eq!(program, vec![Node::number(5)]);
+    assert_eq!(
+        errors,
+        vec![
+            "expected expression, found ';'",
+            "expected ';' after expression, found '3'",
+            "expected ')' to close '(', found ';'",
+        ],
+    );
+
+    // a block goes on after an error in it, a stray '}' is skipped.
+    let (program, errors) = recover("{ 1 + ; 2; } } 3;", 0);
+    assert_eq!(program, vec![Node::block(vec![Node::number(2)]), Node::number(3)]);
+    assert_eq!(
+        errors,
+        vec!["expected expression, found ';'", "expected expression, found '}'"],
+    );
+
+    // the block of a broken statement is skipped with it, its cases are not stray.
+    let (program, errors) = recover("switch 1) { case 1: break; } 2;", 0);
+    assert_eq!(program, vec![Node::number(2)]);
+    assert_eq!(errors, vec!["expected '(' after 'switch', found '1'"]);
+
+    // a label in a skipped statement does not make its gotos undefined.
+    let (_, errors) = recover("goto x; x 1: ;", 0);
+    assert_eq!(errors, vec!["expected ';' after expression, found '1'"]);
+
+    // every undefined label is reported, each once.
+    let (_, errors) = recover("goto a; goto b; goto a; c: goto c;", 0);
+    assert_eq!(
+        errors,
+        vec!["label 'a' used but not defined", "label 'b' used but not defined"],
+    );
+    let (_, errors) = recover("goto a; goto b; goto c;", 2);
+    assert_eq!(errors.len(), 2);
+
+    let (_, errors) = recover("1 +; 2 +; 3 +;", 2);
+    assert_eq!(errors.len(), 2);
+}
+
+#[test]
+fn warning_test() {
+    let warnings = |input| {
+        let (_, warnings) = parse_reporting(tokenize(input).unwrap(), &mut Diagnostics::default());
+        warnings
+            .iter()
+            .map(|w| (w.name(), w.to_string(), w.diagnostic().loc.unwrap().0))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(
+        warnings("a = b + 1; c = 2; c = a; x: ;"),
+        vec![
+            ("unused-label", "label 'x' defined but not used".to_owned(), 25),
+            ("uninitialized", "'b' is used uninitialized".to_owned(), 4),
+            ("unused-variable", "variable 'c' set but not used".to_owned(), 11),
+        ],
+    );
+    // the variable assigned to is read after it is written, in the value of the assignment.
+    assert!(warnings("a = 1; a = a + 1; a;").is_empty());
+
+    assert_eq!(
+        warnings("a = 1; a == a; (a < a) + (a >= a);"),
+        vec![
+            ("tautological-compare", "self-comparison always evaluates to true".to_owned(), 9),
+            ("tautological-compare", "self-comparison always evaluates to false".to_owned(), 18),
+            ("tautological-compare", "self-comparison always evaluates to true".to_owned(), 28),
+        ],
+    );
+
+    // a jump target after a jump is reachable, the statement after it is not.
+    assert_eq!(
+        warnings("goto x; 1; 2; x: 3; { 4; goto y; } 5; y: ;"),
+        vec![
+            ("unreachable-code", "code will never be executed".to_owned(), 8),
+            ("unreachable-code", "code will never be executed".to_owned(), 35),
+        ],
+    );
+    assert!(warnings("switch (1) { case 1: break; case 2: break; default: ; }").is_empty());
+
+    // so is comparing constants, when neither side depends on a variable.
+    assert_eq!(
+        warnings("a = 1; a < 1; 1 > 2; 3 == 1 + 2 != 0;"),
+        vec![
+            ("tautological-compare", "comparison of constants always evaluates to false".to_owned(), 16),
+            ("tautological-compare", "comparison of constants always evaluates to true".to_owned(), 23),
+            ("tautological-compare", "comparison of constants always evaluates to true".to_owned(), 32),
+        ],
+    );
+
+    // the program of a unit with errors is not checked.
+    assert!(warnings("a; 1 +;").is_empty());
+}
+
+#[test]
+fn goto_test() {
+    // 'goto b; b: 1;'
+    let s = vec![
+        Token::keyword(TokenKind::Goto, Loc(0, 4)),
+        Token::ident("b", Loc(5, 6)),
+        Token::semi_colon(Loc(6, 7)),
+        Token::ident("b", Loc(8, 9)),
+        Token::colon(Loc(9, 10)),
+        Token::number(1, Loc(11, 12)),
+        Token::semi_colon(Loc(12, 13)),
+    ];
+    assert_eq!(
+        parse(s).unwrap(),
+        vec![
+            Node::new(Kind::Goto("b".to_owned()), None, None),
+            Node::new(Kind::Label("b".to_owned()), Node::link(Node::number(1)), None),
+        ],
+    );
+
+    // 'a: ; a: ;'
+    let s = vec![
+        Token::ident("a", Loc(0, 1)),
+        Token::colon(Loc(1, 2)),
+        Token::semi_colon(Loc(3, 4)),
+        Token::ident("a", Loc(5, 6)),
+        Token::colon(Loc(6, 7)),
+        Token::semi_colon(Loc(8, 9)),
+    ];
+    assert!(matches!(
+        parse(s),
+        Err(crate::Error::Parser(Error::DuplicateLabel(ref t, ref first))) if t.loc == Loc(5, 6) && first.loc == Loc(0, 1)
+    ));
+
+    // 'a: goto b;'
+    let s = vec![
+        Token::ident("a", Loc(0, 1)),
+        Token::colon(Loc(1, 2)),
+        Token::keyword(TokenKind::Goto, Loc(3, 7)),
+        Token::ident("b", Loc(8, 9)),
+        Token::semi_colon(Loc(9, 10)),
+    ];
+    assert!(matches!(
+        parse(s),
+        Err(crate::Error::Parser(Error::UndefinedLabel(ref t))) if t.loc == Loc(8, 9)
+    ));
+}
+
+#[test]
+fn tree_test() {
+    let program = parse(tokenize("a = 1 ? 2 : 3; switch (a) { case 1: x: goto x; default: ; }").unwrap()).unwrap();
+    assert_eq!(
+        crate::ast::tree(&program),
+        "\
+Assign
+  LocalVar a [rbp-8]
+  Cond
+    Number 1
+    Number 2
+    Number 3
+Switch cases=[1] default
+  LocalVar a [rbp-8]
+  Block
+    Case 1
+      Label x
+        Goto x
+    Default
+      Block
+",
+    );
+}
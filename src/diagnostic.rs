@@ -0,0 +1,235 @@
+use crate::{
+    lex::{token::Loc, Token},
+    source::SourceMap,
+};
+use std::{fmt, slice};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+            Severity::Note => write!(f, "note"),
+        }
+    }
+}
+
+// something to tell about the source of a unit, shown with the lines it is about.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub loc: Option<Loc>,   // primary span, none for the unit as a whole
+    pub labels: Vec<Label>, // secondary spans, each shown as a note at its line
+    pub notes: Vec<String>,
+    pub help: Option<String>,
+    pub flag: Option<&'static str>, // -W name of a warning, shown after the message
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub loc: Loc,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: String) -> Self {
+        Self {
+            severity,
+            message,
+            loc: None,
+            labels: Vec::new(),
+            notes: Vec::new(),
+            help: None,
+            flag: None,
+        }
+    }
+    pub fn error(message: String) -> Self {
+        Diagnostic::new(Severity::Error, message)
+    }
+    pub fn warning(message: String) -> Self {
+        Diagnostic::new(Severity::Warning, message)
+    }
+    pub fn at(self, loc: Loc) -> Self {
+        Self {
+            loc: Some(loc),
+            ..self
+        }
+    }
+    // at token in the source. a token out of a macro is at the outermost invocation,
+    // with a label where it is spelled in the macro.
+    pub fn at_token(self, token: &Token) -> Self {
+        let loc = site(token);
+        match token.expansion {
+            Some(ref expansion) if token.loc != loc => {
+                let message = format!("expanded from macro '{}'", expansion.name);
+                self.at(loc).label(token.loc.clone(), message)
+            }
+            _ => self.at(loc),
+        }
+    }
+    pub fn label(mut self, loc: Loc, message: String) -> Self {
+        self.labels.push(Label { loc, message });
+        self
+    }
+    pub fn note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+    pub fn help(self, help: String) -> Self {
+        Self {
+            help: Some(help),
+            ..self
+        }
+    }
+    pub fn flag(self, flag: &'static str) -> Self {
+        Self {
+            flag: Some(flag),
+            ..self
+        }
+    }
+
+    // shows the diagnostic at its location in the files of sources.
+    pub fn display<'a>(&'a self, sources: &'a SourceMap) -> Display<'a> {
+        Display {
+            diagnostic: self,
+            sources,
+        }
+    }
+}
+
+// where a token is in the source, a token out of a macro is at the outermost invocation.
+pub(crate) fn site(token: &Token) -> Loc {
+    let mut loc = &token.loc;
+    let mut parent = &token.expansion;
+    while let Some(expansion) = parent {
+        loc = &expansion.loc;
+        parent = &expansion.parent;
+    }
+    loc.clone()
+}
+
+// a diagnostic as "file:line:col: severity: message" followed by the line with carets,
+// then its labels the same way and its notes and help.
+pub struct Display<'a> {
+    diagnostic: &'a Diagnostic,
+    sources: &'a SourceMap,
+}
+
+impl fmt::Display for Display<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let d = self.diagnostic;
+        let message = match (d.flag, d.severity) {
+            (Some(flag), Severity::Error) => format!("{} [-Werror={}]", d.message, flag),
+            (Some(flag), _) => format!("{} [-W{}]", d.message, flag),
+            (None, _) => d.message.clone(),
+        };
+        self.snippet(f, d.loc.as_ref(), d.severity, &message)?;
+        for label in &d.labels {
+            writeln!(f)?;
+            self.snippet(f, Some(&label.loc), Severity::Note, &label.message)?;
+        }
+        for note in &d.notes {
+            write!(f, "\nnote: {}", note)?;
+        }
+        if let Some(ref help) = d.help {
+            write!(f, "\nhelp: {}", help)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display<'_> {
+    // a message without a location is reported against the main file.
+    fn snippet(
+        &self,
+        f: &mut fmt::Formatter,
+        loc: Option<&Loc>,
+        severity: Severity,
+        message: &str,
+    ) -> fmt::Result {
+        let (loc, pos) = match loc.and_then(|loc| Some((loc, self.sources.position(loc.0)?))) {
+            Some(found) => found,
+            None => return write!(f, "{}: {}: {}", self.sources.main().name, severity, message),
+        };
+        let text = pos.file.line_text(pos.line);
+        // carets stop at the end of the line, one under each char whatever its bytes.
+        let start = pos.col - 1;
+        let end = (start + loc.1 - loc.0).min(text.len());
+        let chars = |s: Option<&str>, bytes: usize| s.map_or(bytes, |s| s.chars().count());
+        let indent = chars(text.get(..start), start);
+        let width = chars(text.get(start..end), end.saturating_sub(start)).max(1);
+        // the line is shown as it is, under the name and number #line gives it.
+        let (name, line) = pos.file.presumed(pos.line);
+        writeln!(f, "{}:{}:{}: {}: {}", name, line, pos.col, severity, message)?;
+        writeln!(f, "{}", text)?;
+        write!(f, "{}{}", " ".repeat(indent), "^".repeat(width))
+    }
+}
+
+// the diagnostics of a translation unit in the order reported. stages which can go on
+// after an error stop once max_errors errors are reported, 0 is no limit.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics {
+    reported: Vec<Diagnostic>,
+    max_errors: usize,
+    werror: bool,    // warnings are reported as errors
+    promoted: usize, // warnings reported as errors
+}
+
+impl Diagnostics {
+    pub fn new(max_errors: usize) -> Self {
+        Self {
+            reported: Vec::new(),
+            max_errors,
+            werror: false,
+            promoted: 0,
+        }
+    }
+    pub fn werror(self, werror: bool) -> Self {
+        Self { werror, ..self }
+    }
+    pub fn report(&mut self, mut diagnostic: Diagnostic) {
+        if self.werror && diagnostic.severity == Severity::Warning {
+            diagnostic.severity = Severity::Error;
+            self.promoted += 1;
+        }
+        self.reported.push(diagnostic);
+    }
+    pub fn error_count(&self) -> usize {
+        self.reported
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count()
+    }
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+    // errors other than warnings reported as errors, which a stage cannot go on after.
+    pub fn has_hard_errors(&self) -> bool {
+        self.error_count() > self.promoted
+    }
+    pub fn limit_reached(&self) -> bool {
+        self.max_errors > 0 && self.error_count() >= self.max_errors
+    }
+    pub fn max_errors(&self) -> usize {
+        self.max_errors
+    }
+    pub fn is_empty(&self) -> bool {
+        self.reported.is_empty()
+    }
+    pub fn iter(&self) -> slice::Iter<'_, Diagnostic> {
+        self.reported.iter()
+    }
+}
+
+#[cfg(test)]
+#[path = "./diagnostic_test.rs"]
+mod diagnostic_test;
@@ -0,0 +1,67 @@
+use super::*;
+
+#[test]
+fn display_test() {
+    let mut sources = SourceMap::new("a.c", "a: ;\nb: ;\na: ;\n");
+    let base = sources.add("a.h", "x @\n".to_owned()).base;
+
+    let d = Diagnostic::error("duplicate label 'a'".to_owned())
+        .at(Loc(10, 11))
+        .label(Loc(0, 1), "previous definition is here".to_owned())
+        .note("labels are function scoped".to_owned())
+        .help("rename one of them".to_owned());
+    assert_eq!(
+        d.display(&sources).to_string(),
+        "\
+a.c:3:1: error: duplicate label 'a'
+a: ;
+^
+a.c:1:1: note: previous definition is here
+a: ;
+^
+note: labels are function scoped
+help: rename one of them",
+    );
+
+    let d = Diagnostic::warning("stray '@'".to_owned()).at(Loc(base + 2, base + 3));
+    assert_eq!(d.display(&sources).to_string(), "a.h:1:3: warning: stray '@'\nx @\n  ^");
+
+    // the column counts bytes, the carets are under chars.
+    let base = sources.add("b.h", "é = €;\n".to_owned()).base;
+    let d = Diagnostic::error("invalid char '€'".to_owned()).at(Loc(base + 5, base + 8));
+    assert_eq!(d.display(&sources).to_string(), "b.h:1:6: error: invalid char '€'\né = €;\n    ^");
+
+    // a warning under a -W flag names it, as -Werror when it is an error.
+    let d = Diagnostic::warning("unused".to_owned()).flag("unused-label");
+    assert_eq!(d.display(&sources).to_string(), "a.c: warning: unused [-Wunused-label]");
+    let d = Diagnostic::error("unused".to_owned()).flag("unused-label");
+    assert_eq!(d.display(&sources).to_string(), "a.c: error: unused [-Werror=unused-label]");
+
+    // without a location it is about the main file.
+    let d = Diagnostic::error("no main".to_owned());
+    assert_eq!(d.display(&sources).to_string(), "a.c: error: no main");
+}
+
+#[test]
+fn diagnostics_test() {
+    let mut diagnostics = Diagnostics::new(2);
+    assert!(diagnostics.is_empty());
+    diagnostics.report(Diagnostic::warning("w".to_owned()));
+    diagnostics.report(Diagnostic::error("e".to_owned()));
+    assert_eq!(diagnostics.error_count(), 1);
+    assert!(!diagnostics.limit_reached());
+    diagnostics.report(Diagnostic::error("e".to_owned()));
+    assert!(diagnostics.limit_reached());
+    assert_eq!(diagnostics.iter().count(), 3);
+
+    // -Werror, a warning is an error.
+    let mut diagnostics = Diagnostics::new(0).werror(true);
+    diagnostics.report(Diagnostic::warning("w".to_owned()));
+    assert_eq!(diagnostics.error_count(), 1);
+    assert!(diagnostics.has_errors() && !diagnostics.has_hard_errors());
+
+    // no limit.
+    let mut diagnostics = Diagnostics::new(0);
+    diagnostics.report(Diagnostic::error("e".to_owned()));
+    assert!(diagnostics.has_errors() && !diagnostics.limit_reached());
+}
@@ -0,0 +1,405 @@
+mod options;
+
+use crate::{ast, diagnostic::site, generate, lex, pp, Diagnostics, SourceMap};
+use options::{Emit, Options, Stage};
+use std::{
+    env,
+    error::Error as StdError,
+    fmt, fs,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{self, Command, ExitStatus},
+    result::Result as StdResult,
+};
+
+#[derive(Debug)]
+pub enum Error {
+    NoInput,
+    OutputForMultipleInputs, // -o with -E, -S or -c
+    MissingArgument(String), // option expecting a value
+    InvalidArgument(String, String), // option and its value
+    UnknownOption(String),
+    UnsupportedEmit(String), // --emit kind without such a stage
+    Read(String, io::Error), // input name
+    Write(PathBuf, io::Error),
+    Compile(Box<Unit>),                   // a unit which failed, with what was reported in it
+    Spawn(String, io::Error),             // external tool which could not be run
+    Command(String, ExitStatus),          // external tool which failed
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            NoInput => write!(f, "no input files"),
+            OutputForMultipleInputs => write!(
+                f,
+                "cannot specify '-o' with '-c', '-S' or '-E' with multiple files"
+            ),
+            MissingArgument(option) => write!(f, "missing argument to '{}'", option),
+            InvalidArgument(option, value) => write!(f, "invalid argument '{}' to '{}'", value, option),
+            UnknownOption(option) => write!(f, "unrecognized command-line option '{}'", option),
+            UnsupportedEmit(kind) => write!(
+                f,
+                "cannot emit '{}', the stages are tokens, ast and asm",
+                kind
+            ),
+            Read(name, e) => write!(f, "{}: {}", name, e),
+            Write(path, e) => write!(f, "{}: {}", path.display(), e),
+            Compile(unit) => write!(f, "{}", unit),
+            Spawn(program, e) => write!(f, "{}: {}", program, e),
+            Command(program, status) => write!(f, "{} failed with {}", program, status),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+type Result<T> = StdResult<T, Error>;
+
+// runs r9cc with the command line args, without the program name. returns the exit code.
+pub fn run(args: &[String]) -> i32 {
+    let errors = match Options::parse(args) {
+        Ok(options) => Driver::new(options).run(),
+        Err(e) => vec![e],
+    };
+    for e in &errors {
+        match e {
+            Error::Compile(..) => eprintln!("{}", e),
+            _ => eprintln!("r9cc: error: {}", e),
+        }
+    }
+    if errors.is_empty() {
+        0
+    } else {
+        1
+    }
+}
+
+// what an input file is, by its extension like gcc.
+enum Input {
+    Source,   // .c, or stdin
+    Assembly, // .s
+    Linker,   // anything else, objects and libraries
+}
+
+impl Input {
+    fn of(name: &str) -> Self {
+        match Path::new(name).extension().and_then(|ext| ext.to_str()) {
+            _ if name == "-" => Input::Source,
+            Some("c") | Some("i") => Input::Source,
+            Some("s") => Input::Assembly,
+            _ => Input::Linker,
+        }
+    }
+}
+
+struct Driver {
+    options: Options,
+    config: pp::Config,
+    temps: Vec<PathBuf>, // intermediate files, removed when done
+}
+
+impl Drop for Driver {
+    fn drop(&mut self) {
+        for temp in &self.temps {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+impl Driver {
+    fn new(options: Options) -> Self {
+        let config = pp::Config {
+            include_paths: options.include_paths.clone(),
+            defines: options.defines.clone(),
+        };
+        Self {
+            options,
+            config,
+            temps: Vec::new(),
+        }
+    }
+
+    // translates each input on its own, then links them all if no unit failed.
+    fn run(&mut self) -> Vec<Error> {
+        let mut errors = Vec::new();
+        let mut objects = Vec::new();
+        for input in self.options.inputs.clone() {
+            match self.unit(&input) {
+                Ok(object) => objects.extend(object),
+                Err(e) => errors.push(e),
+            }
+        }
+        if errors.is_empty() && self.options.stage == Stage::Executable {
+            let output = self.options.output.clone().unwrap_or_else(|| "a.out".into());
+            errors.extend(self.link(&objects, &output).err());
+        }
+        errors
+    }
+
+    // runs the stages for an input, returns the file to link if linking.
+    fn unit(&mut self, input: &str) -> Result<Option<PathBuf>> {
+        let stage = self.options.stage;
+        let asm = match Input::of(input) {
+            // files which are not read before linking are left to the linker.
+            Input::Linker => return Ok(Some(input.into()).filter(|_| stage == Stage::Executable)),
+            Input::Assembly if stage != Stage::Object && stage != Stage::Executable => {
+                return Ok(None)
+            }
+            Input::Assembly => PathBuf::from(input),
+            Input::Source => {
+                let (name, text) = read(input)?;
+                let mut unit = Unit {
+                    sources: SourceMap::new(&name, &text),
+                    diagnostics: Diagnostics::new(self.options.max_errors)
+                        .werror(self.options.warnings.error),
+                };
+                let asm = self.source(input, &mut unit);
+                // the warnings of a unit which failed are shown with its errors.
+                if asm.is_ok() && !unit.diagnostics.is_empty() {
+                    eprintln!("{}", unit);
+                }
+                match asm? {
+                    Some(asm) => asm,
+                    None => return Ok(None),
+                }
+            }
+        };
+        match stage {
+            Stage::Object => assemble(&asm, &self.output(input, "o")).map(|_| None),
+            Stage::Executable => {
+                let object = self.temp("o");
+                assemble(&asm, &object)?;
+                Ok(Some(object))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    // runs the stages for a source file up to assembly, returns the assembly if the stage
+    // after it is run.
+    fn source(&mut self, input: &str, unit: &mut Unit) -> Result<Option<PathBuf>> {
+        let stage = self.options.stage;
+        let stdout = || self.options.output.clone().unwrap_or_else(|| "-".into());
+        // nothing is written for a unit with errors, -Werror ones included.
+        match stage {
+            Stage::Preprocess => {
+                let text = self.preprocess(unit)?;
+                unit.finish()?;
+                write(&stdout(), text.as_bytes())?;
+                return Ok(None);
+            }
+            Stage::Emit(emit) => {
+                let bytes = self.emit(emit, unit)?;
+                unit.finish()?;
+                write(&stdout(), &bytes)?;
+                return Ok(None);
+            }
+            _ => (),
+        }
+        let bytes = self.compile(unit)?;
+        unit.finish()?;
+        let asm = match stage {
+            Stage::Assembly => self.output(input, "s"),
+            _ => self.temp("s"),
+        };
+        write(&asm, &bytes)?;
+        Ok(Some(asm))
+    }
+
+    fn preprocess(&self, unit: &mut Unit) -> Result<String> {
+        let tokens = self.lex(unit);
+        let text = pp::preprocess_text(&mut unit.sources, tokens, &self.config, &mut unit.diagnostics)
+            .map_err(|e| unit.fail(e))?;
+        unit.check()?;
+        Ok(text)
+    }
+
+    // compiles the main file of a unit to assembly.
+    fn compile(&self, unit: &mut Unit) -> Result<Vec<u8>> {
+        let program = self.parse(unit)?;
+        let mut asm = Vec::new();
+        generate(&mut asm, program).map_err(|e| unit.fail(e))?;
+        Ok(asm)
+    }
+
+    // the tokens of the main file, its lexical errors are reported by the preprocessor.
+    fn lex(&self, unit: &mut Unit) -> lex::Stream {
+        lex::tokenize_deferred(&unit.sources.main().text, 0)
+    }
+
+    // the preprocessed tokens, every lexical error outside of skipped groups is reported.
+    fn tokens(&self, unit: &mut Unit) -> Result<lex::Stream> {
+        let tokens = self.lex(unit);
+        let tokens = pp::preprocess(&mut unit.sources, tokens, &self.config, &mut unit.diagnostics)
+            .map_err(|e| unit.fail(e))?;
+        unit.check()?;
+        Ok(tokens)
+    }
+
+    // the program of the main file, every syntax error in it is reported.
+    fn parse(&self, unit: &mut Unit) -> Result<ast::Program> {
+        let tokens = self.tokens(unit)?;
+        let (program, warnings) = ast::parse_reporting(tokens, &mut unit.diagnostics);
+        for warning in &warnings {
+            self.warn(unit, warning);
+        }
+        unit.check()?;
+        Ok(program)
+    }
+
+    // reports warning if its -W flag is on.
+    fn warn(&self, unit: &mut Unit, warning: &ast::Warning) {
+        if self.options.warnings.is_enabled(warning.name()) {
+            unit.diagnostics.report(warning.diagnostic());
+        }
+    }
+
+    // the output of a stage as text, to see what went wrong in which.
+    fn emit(&self, emit: Emit, unit: &mut Unit) -> Result<Vec<u8>> {
+        match emit {
+            Emit::Tokens => {
+                let mut s = String::new();
+                for token in self.tokens(unit)? {
+                    let spelling = match token.value {
+                        lex::TokenKind::Str(ref lit) => lit.to_string(),
+                        ref kind => kind.to_string(),
+                    };
+                    s.push_str(&format!("{}: {}", unit.locate(&token), spelling));
+                    if let Some(ref expansion) = token.expansion {
+                        s.push_str(&format!(" from {}", expansion.name));
+                    }
+                    s.push('\n');
+                }
+                Ok(s.into_bytes())
+            }
+            Emit::Ast => Ok(ast::tree(&self.parse(unit)?).into_bytes()),
+            Emit::Asm => self.compile(unit),
+        }
+    }
+
+    fn link(&self, objects: &[PathBuf], output: &Path) -> Result<()> {
+        let mut cc = Command::new("cc");
+        cc.arg("-o").arg(output).args(objects).args(&self.options.link_args);
+        command(cc)
+    }
+
+    // -o, or the input file name with ext in the current directory like gcc.
+    fn output(&self, input: &str, ext: &str) -> PathBuf {
+        match self.options.output {
+            Some(ref output) => output.clone(),
+            None => {
+                let stem = Path::new(input).file_stem().unwrap_or_default();
+                Path::new(stem).with_extension(ext)
+            }
+        }
+    }
+
+    fn temp(&mut self, ext: &str) -> PathBuf {
+        let name = format!("r9cc-{}-{}.{}", process::id(), self.temps.len(), ext);
+        let path = env::temp_dir().join(name);
+        self.temps.push(path.clone());
+        path
+    }
+}
+
+// a translation unit being compiled, the files read for it and what was reported in them.
+#[derive(Debug, Clone)]
+pub struct Unit {
+    sources: SourceMap,
+    diagnostics: Diagnostics,
+}
+
+impl Unit {
+    // reports e, which the unit cannot go on after.
+    fn fail(&mut self, e: crate::Error) -> Error {
+        self.diagnostics.report(e.diagnostic(&self.sources));
+        self.error()
+    }
+
+    fn error(&self) -> Error {
+        Error::Compile(Box::new(self.clone()))
+    }
+
+    // fails if an error was reported which the next stage cannot go on after.
+    fn check(&self) -> Result<()> {
+        if self.diagnostics.has_hard_errors() {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+
+    // where token is as "file:line:col", at the macro use it came from like a diagnostic.
+    fn locate(&self, token: &lex::Token) -> String {
+        let pos = match self.sources.position(site(token).0) {
+            Some(pos) => pos,
+            None => return self.sources.main().name.clone(),
+        };
+        let (name, line) = pos.file.presumed(pos.line);
+        format!("{}:{}:{}", name, line, pos.col)
+    }
+
+    // fails if any error was reported, when the unit is done.
+    fn finish(&self) -> Result<()> {
+        if self.diagnostics.has_errors() {
+            return Err(self.error());
+        }
+        Ok(())
+    }
+}
+
+// every diagnostic of the unit, shown at its place in the files.
+impl fmt::Display for Unit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (i, d) in self.diagnostics.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", d.display(&self.sources))?;
+        }
+        if self.diagnostics.limit_reached() {
+            let n = self.diagnostics.max_errors();
+            write!(f, "\ncompilation terminated due to -fmax-errors={}.", n)?;
+        }
+        Ok(())
+    }
+}
+
+// reads an input file, "-" is stdin. returns the name for diagnostics and the text.
+fn read(input: &str) -> Result<(String, String)> {
+    if input == "-" {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .map_err(|e| Error::Read("<stdin>".to_owned(), e))?;
+        return Ok(("<stdin>".to_owned(), text));
+    }
+    let text = fs::read_to_string(input).map_err(|e| Error::Read(input.to_owned(), e))?;
+    Ok((input.to_owned(), text))
+}
+
+// writes an output file, "-" is stdout.
+fn write(path: &Path, bytes: &[u8]) -> Result<()> {
+    let result = if path == Path::new("-") {
+        io::stdout().write_all(bytes)
+    } else {
+        fs::write(path, bytes)
+    };
+    result.map_err(|e| Error::Write(path.to_owned(), e))
+}
+
+fn assemble(asm: &Path, object: &Path) -> Result<()> {
+    let mut cmd = Command::new("as");
+    cmd.arg("-o").arg(object).arg(asm);
+    command(cmd)
+}
+
+fn command(mut cmd: Command) -> Result<()> {
+    let program = cmd.get_program().to_string_lossy().into_owned();
+    let status = cmd.status().map_err(|e| Error::Spawn(program.clone(), e))?;
+    if !status.success() {
+        return Err(Error::Command(program, status));
+    }
+    Ok(())
+}
@@ -0,0 +1,177 @@
+use super::Error;
+use crate::pp::Define;
+use std::{path::PathBuf, result::Result as StdResult};
+
+// where to stop, the output is the result of the last stage run.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Stage {
+    Preprocess, // -E
+    Assembly,   // -S
+    Object,     // -c
+    Executable,
+    Emit(Emit), // --emit, dumps what a stage produced
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Emit {
+    Tokens, // the stream after preprocessing
+    Ast,
+    Asm,
+}
+
+#[derive(Debug, PartialEq)]
+pub(super) struct Options {
+    pub(super) stage: Stage,
+    pub(super) output: Option<PathBuf>, // -o, "-" is stdout
+    pub(super) inputs: Vec<String>,     // "-" is stdin, compiled in order
+    pub(super) include_paths: Vec<PathBuf>,
+    pub(super) defines: Vec<Define>,
+    pub(super) link_args: Vec<String>, // -L and -l in order, passed to the linker
+    pub(super) max_errors: usize,      // -fmax-errors, errors a unit reports before it stops
+    pub(super) warnings: Warnings,
+}
+
+// the -W names of the warnings -Wall turns on, and those -Wextra turns on besides.
+const ALL: [&str; 4] = ["unused-variable", "unused-label", "uninitialized", "tautological-compare"];
+const EXTRA: [&str; 1] = ["unreachable-code"];
+
+// which warnings are reported, all are off unless turned on.
+#[derive(Debug, Default, PartialEq)]
+pub(super) struct Warnings {
+    pub(super) enabled: Vec<&'static str>, // -W names
+    pub(super) error: bool,                // -Werror, warnings are reported as errors
+}
+
+impl Warnings {
+    // applies -Wflag, or -Wno-flag which undoes it. false if there is no such flag.
+    fn set(&mut self, flag: &str) -> bool {
+        let (on, name) = match flag.strip_prefix("no-") {
+            Some(name) => (false, name),
+            None => (true, flag),
+        };
+        let names: Vec<&'static str> = match name {
+            "error" => {
+                self.error = on;
+                return true;
+            }
+            "all" => ALL.to_vec(),
+            "extra" => EXTRA.to_vec(),
+            _ => match ALL.iter().chain(EXTRA.iter()).find(|known| **known == name) {
+                Some(known) => vec![*known],
+                None => return false,
+            },
+        };
+        self.enabled.retain(|enabled| !names.contains(enabled));
+        if on {
+            self.enabled.extend(names);
+        }
+        true
+    }
+
+    pub(super) fn is_enabled(&self, name: &str) -> bool {
+        self.enabled.contains(&name)
+    }
+}
+
+// flags of common gcc build lines which change nothing here.
+fn is_ignored(arg: &str) -> bool {
+    match arg {
+        "-O" | "-O0" | "-O1" | "-O2" | "-O3" | "-Os" | "-Og" | "-Oz" | "-Ofast" => true,
+        "-g" | "-g0" | "-g1" | "-g2" | "-g3" | "-ggdb" => true,
+        "-pipe" | "-pedantic" => true,
+        _ => arg.starts_with("-std="),
+    }
+}
+
+impl Options {
+    // parses the command line without the program name, gcc style.
+    pub(super) fn parse(args: &[String]) -> StdResult<Options, Error> {
+        let mut options = Options {
+            stage: Stage::Executable,
+            output: None,
+            inputs: Vec::new(),
+            include_paths: Vec::new(),
+            defines: Vec::new(),
+            link_args: Vec::new(),
+            max_errors: 0,
+            warnings: Warnings::default(),
+        };
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            // options taking a value accept it joined, -Idir, or as the next argument, -I dir.
+            let mut value = |name: &str| -> StdResult<String, Error> {
+                match &arg[name.len()..] {
+                    "" => args
+                        .next()
+                        .cloned()
+                        .ok_or_else(|| Error::MissingArgument(name.to_owned())),
+                    joined => Ok(joined.to_owned()),
+                }
+            };
+            match arg.as_str() {
+                "-E" => options.stage = Stage::Preprocess,
+                "-S" => options.stage = Stage::Assembly,
+                "-c" => options.stage = Stage::Object,
+                "-" => options.inputs.push(arg.clone()),
+                _ if arg.starts_with("--emit=") => {
+                    let emit = match &arg["--emit=".len()..] {
+                        "tokens" => Emit::Tokens,
+                        "ast" => Emit::Ast,
+                        "asm" => Emit::Asm,
+                        kind => return Err(Error::UnsupportedEmit(kind.to_owned())),
+                    };
+                    options.stage = Stage::Emit(emit);
+                }
+                _ if arg.starts_with("-fmax-errors=") => {
+                    let n = &arg["-fmax-errors=".len()..];
+                    options.max_errors = n
+                        .parse()
+                        .map_err(|_| Error::InvalidArgument("-fmax-errors=".to_owned(), n.to_owned()))?;
+                }
+                // later flags win, -Wall -Wno-unused-label is all but unused-label. like gcc, a
+                // -Wno- flag for a warning there is not is ignored.
+                _ if arg.starts_with("-W") => {
+                    if !options.warnings.set(&arg[2..]) && !arg.starts_with("-Wno-") {
+                        return Err(Error::UnknownOption(arg.clone()));
+                    }
+                }
+                // there is no optimizer and no debug info, nor any other standard to pick.
+                _ if is_ignored(arg) => (),
+                _ if arg.starts_with("-o") => options.output = Some(value("-o")?.into()),
+                _ if arg.starts_with("-I") => options.include_paths.push(value("-I")?.into()),
+                _ if arg.starts_with("-D") => {
+                    let define = value("-D")?;
+                    // -DNAME defines NAME as 1 like gcc.
+                    let (name, body) = match define.find('=') {
+                        Some(i) => (&define[..i], &define[i + 1..]),
+                        None => (define.as_str(), "1"),
+                    };
+                    options
+                        .defines
+                        .push(Define::Macro(name.to_owned(), body.to_owned()));
+                }
+                _ if arg.starts_with("-U") => options.defines.push(Define::Undef(value("-U")?)),
+                _ if arg.starts_with("-L") => options.link_args.push(format!("-L{}", value("-L")?)),
+                _ if arg.starts_with("-l") => options.link_args.push(format!("-l{}", value("-l")?)),
+                _ if arg.starts_with('-') => return Err(Error::UnknownOption(arg.clone())),
+                _ => options.inputs.push(arg.clone()),
+            }
+        }
+
+        if options.inputs.is_empty() {
+            return Err(Error::NoInput);
+        }
+        // every unit has its own output unless they are linked together.
+        if options.inputs.len() > 1
+            && options.output.is_some()
+            && options.stage != Stage::Executable
+        {
+            return Err(Error::OutputForMultipleInputs);
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+#[path = "./options_test.rs"]
+mod options_test;
@@ -0,0 +1,76 @@
+use super::*;
+
+#[test]
+fn parse_test() {
+    assert_eq!(
+        parse("a.c").unwrap(),
+        Options {
+            stage: Stage::Executable,
+            output: None,
+            inputs: vec!["a.c".to_owned()],
+            include_paths: Vec::new(),
+            defines: Vec::new(),
+            link_args: Vec::new(),
+            max_errors: 0,
+            warnings: Warnings::default(),
+        },
+    );
+
+    let options = parse("-S -o out.s -Iinc -I /usr/inc -DN -D M=2 -UN -DF(x)=x -Llib -l m -").unwrap();
+    assert_eq!(options.stage, Stage::Assembly);
+    assert_eq!(options.output, Some("out.s".into()));
+    assert_eq!(options.inputs, vec!["-"]);
+    assert_eq!(options.include_paths, vec![PathBuf::from("inc"), "/usr/inc".into()]);
+    assert_eq!(
+        options.defines,
+        vec![
+            Define::Macro("N".to_owned(), "1".to_owned()),
+            Define::Macro("M".to_owned(), "2".to_owned()),
+            Define::Undef("N".to_owned()),
+            Define::Macro("F(x)".to_owned(), "x".to_owned()),
+        ],
+    );
+    assert_eq!(options.link_args, vec!["-Llib", "-lm"]);
+    assert_eq!(parse("-fmax-errors=3 a.c").unwrap().max_errors, 3);
+
+    let warnings = parse("-Wall -Wno-unused-label -Wunreachable-code -Werror a.c").unwrap().warnings;
+    assert_eq!(warnings.enabled, vec!["unused-variable", "uninitialized", "tautological-compare", "unreachable-code"]);
+    assert!(warnings.error);
+    let warnings = parse("-Wextra -Wunreachable-code -Wno-extra -Werror -Wno-error a.c").unwrap().warnings;
+    assert_eq!(warnings, Warnings::default());
+
+    // flags of an ordinary build line, and -Wno- of unknown warnings, change nothing.
+    assert_eq!(
+        parse("-O2 -g -std=c11 -pipe -Wall -Wno-such a.c").unwrap(),
+        parse("-Wall a.c").unwrap(),
+    );
+
+    assert_eq!(parse("a.c -o a b.o").unwrap().inputs, vec!["a.c", "b.o"]);
+    assert_eq!(parse("-S a.c b.c").unwrap().inputs, vec!["a.c", "b.c"]);
+
+    // the last stage option wins.
+    assert_eq!(parse("-E -c a.c").unwrap().stage, Stage::Object);
+    assert_eq!(parse("a.c -E").unwrap().stage, Stage::Preprocess);
+    assert_eq!(parse("--emit=ast a.c").unwrap().stage, Stage::Emit(Emit::Ast));
+    assert_eq!(parse("--emit=tokens -S a.c").unwrap().stage, Stage::Assembly);
+}
+
+#[test]
+fn parse_error_test() {
+    assert!(matches!(parse(""), Err(Error::NoInput)));
+    assert!(matches!(parse("-c"), Err(Error::NoInput)));
+    assert!(matches!(parse("-c -o a.o a.c b.c"), Err(Error::OutputForMultipleInputs)));
+    assert!(matches!(parse("a.c -o"), Err(Error::MissingArgument(ref o)) if o == "-o"));
+    assert!(matches!(parse("-x a.c"), Err(Error::UnknownOption(ref o)) if o == "-x"));
+    assert!(matches!(parse("-Wsuch a.c"), Err(Error::UnknownOption(ref o)) if o == "-Wsuch"));
+    assert!(matches!(parse("--emit=ir a.c"), Err(Error::UnsupportedEmit(ref k)) if k == "ir"));
+    assert!(matches!(
+        parse("-fmax-errors=x a.c"),
+        Err(Error::InvalidArgument(ref o, ref v)) if o == "-fmax-errors=" && v == "x"
+    ));
+}
+
+fn parse(args: &str) -> StdResult<Options, Error> {
+    let args: Vec<String> = args.split_whitespace().map(str::to_owned).collect();
+    Options::parse(&args)
+}
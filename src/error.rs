@@ -1,10 +1,16 @@
-use crate::{ast, lex, asm};
+use crate::{
+    asm, ast,
+    diagnostic::Diagnostic,
+    lex::{self, token::Loc},
+    pp,
+    source::SourceMap,
+};
 use std::{error::Error as StdError, fmt};
 
 #[derive(Debug)]
 pub enum Error {
-    InputRequired,
     Lexer(lex::Error),
+    Preprocessor(pp::Error),
     Parser(ast::Error),
     Asm(asm::Error),
 }
@@ -13,29 +19,73 @@ impl fmt::Display for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use Error::*;
         match self {
-            InputRequired => write!(f, "input required"),
-            _ => write!(f, "{:?}", self),
+            Lexer(e) => write!(f, "{}", e),
+            Preprocessor(e) => write!(f, "{}", e),
+            Parser(e) => write!(f, "{}", e),
+            Asm(e) => write!(f, "{}", e),
         }
     }
 }
 
+impl Error {
+    // shows the error at its location in the files of sources.
+    pub fn display<'a>(&self, sources: &'a SourceMap) -> Display<'a> {
+        Display {
+            diagnostic: self.diagnostic(sources),
+            sources,
+        }
+    }
+
+    // a parser running out of tokens is at the end of the main file of sources.
+    pub fn diagnostic(&self, sources: &SourceMap) -> Diagnostic {
+        use Error::*;
+        match self {
+            Lexer(e) => e.diagnostic(),
+            Preprocessor(e) => e.diagnostic(),
+            Parser(ast::Error::Eof) => {
+                let end = sources.main().text.len();
+                ast::Error::Eof.diagnostic().at(Loc(end, end))
+            }
+            Parser(e) => e.diagnostic(),
+            Asm(e) => e.diagnostic(),
+        }
+    }
+}
+
+pub struct Display<'a> {
+    diagnostic: Diagnostic,
+    sources: &'a SourceMap,
+}
+
+impl fmt::Display for Display<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.diagnostic.display(self.sources))
+    }
+}
+
 impl StdError for Error {
     fn source(&self) -> Option<&(dyn StdError + 'static)> {
         use Error::*;
         match *self {
             Lexer(ref e) => Some(e),
+            Preprocessor(ref e) => Some(e),
             Parser(ref e) => Some(e),
             _ => None,
         }
     }
 }
-
 impl From<lex::Error> for Error {
     fn from(e: lex::Error) -> Self {
         Error::Lexer(e)
     }
 }
 
+impl From<pp::Error> for Error {
+    fn from(e: pp::Error) -> Self {
+        Error::Preprocessor(e)
+    }
+}
+
 impl From<ast::Error> for Error {
     fn from(e: ast::Error) -> Self {
         Error::Parser(e)
@@ -47,3 +97,7 @@ impl From<asm::Error> for Error {
          Error::Asm(e)
     }
 }
+
+#[cfg(test)]
+#[path = "./error_test.rs"]
+mod error_test;
@@ -0,0 +1,116 @@
+use super::*;
+use crate::{parse, preprocess, tokenize, Diagnostics, PpConfig};
+use std::{env, fs, process};
+
+#[test]
+fn display_test() {
+    assert_eq!(
+        compile("a.c", "1;\na = @;\n"),
+        "a.c:2:5: error: invalid char '@'\na = @;\n    ^",
+    );
+
+    // an error in a macro is shown at the invocation, and where it is in the macro.
+    assert_eq!(
+        compile("a.c", "#define F(x) x\n#define G F(1, 2)\n  G;"),
+        "a.c:3:3: error: macro given 2 arguments, but takes 1\n  G;\n  ^\n\
+         a.c:2:11: note: expanded from macro 'G'\n#define G F(1, 2)\n          ^",
+    );
+
+    assert_eq!(
+        compile("a.c", "a: ;\na: ;"),
+        "a.c:2:1: error: duplicate label 'a'\na: ;\n^\n\
+         a.c:1:1: note: previous definition is here\na: ;\n^",
+    );
+
+    assert_eq!(
+        compile("-", "1 +\n"),
+        "-:2:1: error: expected expression, found end of input\n\n^",
+    );
+
+    assert_eq!(
+        compile("a.c", "a = (1 + 2;\n"),
+        "a.c:1:11: error: expected ')' to close '(', found ';'\na = (1 + 2;\n          ^",
+    );
+
+    assert_eq!(
+        compile("a.c", "{ 1; (2)) }"),
+        "a.c:1:9: error: expected ';' after expression, found ')'\n{ 1; (2)) }\n        ^",
+    );
+
+    assert_eq!(
+        compile("a.c", "{ 1;"),
+        "a.c:1:5: error: expected '}' at end of block, found end of input\n{ 1;\n    ^",
+    );
+
+    // the line is shown where #line says it is.
+    assert_eq!(
+        compile("l.c", "#line 100 \"gen.y\"\n1 + ;\n"),
+        "gen.y:100:5: error: expected expression, found ';'\n1 + ;\n    ^",
+    );
+    assert_eq!(
+        compile("l.c", "#line 100 \"gen.y\"\n1;\n#line 7\n2 3;\n"),
+        "gen.y:7:3: error: expected ';' after expression, found '3'\n2 3;\n  ^",
+    );
+
+    // carets stop at the end of the line.
+    assert_eq!(
+        compile("a.c", "1;\n/* 2;\n3;"),
+        "a.c:2:1: error: unterminated comment\n/* 2;\n^^",
+    );
+
+    // a stream without the Eof sentinel ends the parse at the end of input.
+    let error = Error::Parser(crate::ast::Error::Eof);
+    assert_eq!(
+        error.display(&SourceMap::new("a.c", "1 +")).to_string(),
+        "a.c:1:4: error: unexpected end of input\n1 +\n   ^",
+    );
+
+    let error = Error::Asm(asm::Error::UnexpectedNode(crate::ast::Kind::Break));
+    assert_eq!(
+        error.display(&SourceMap::new("a.c", "")).to_string(),
+        "a.c: error: unexpected node Break",
+    );
+}
+
+#[test]
+fn include_display_test() {
+    let dir = env::temp_dir().join(format!("r9cc-{}-include_display", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("a.h"), "#define ONE 1\n\n  1 + ;\n").unwrap();
+    fs::write(dir.join("b.h"), "\n  @\n").unwrap();
+
+    let main = dir.join("main.c").display().to_string();
+    assert_eq!(
+        compile(&main, "1;\n#include \"a.h\"\n"),
+        format!(
+            "{}:3:7: error: expected expression, found ';'\n  1 + ;\n      ^",
+            dir.join("a.h").display(),
+        ),
+    );
+    assert_eq!(
+        compile(&main, "1;\n#include \"b.h\"\n"),
+        format!("{}:2:3: error: invalid char '@'\n  @\n  ^", dir.join("b.h").display()),
+    );
+    // the main file after an include is still located in it.
+    fs::write(dir.join("c.h"), "#define TWO 2\n").unwrap();
+    assert_eq!(
+        compile(&main, "#include \"c.h\"\nTWO +"),
+        format!("{}:2:6: error: expected expression, found end of input\nTWO +\n     ^", main),
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+// the error compiling input in the file named name, as shown to the user.
+fn compile(name: &str, input: &str) -> String {
+    let mut sources = SourceMap::new(name, input);
+    let mut diagnostics = Diagnostics::default();
+    let result = tokenize(input)
+        .and_then(|tokens| preprocess(&mut sources, tokens, &PpConfig::default(), &mut diagnostics))
+        .and_then(parse);
+    // lexical errors in included files are reported rather than returned.
+    match diagnostics.iter().next() {
+        Some(d) => d.display(&sources).to_string(),
+        None => result.unwrap_err().display(&sources).to_string(),
+    }
+}
@@ -1,3 +1,4 @@
 pub mod token;
 
-pub use token::{tokenize, Error, Stream, Token, TokenKind, Ident};
+pub use token::{tokenize, tokenize_deferred, Encoding, Error, Expansion, Ident, StrLit, Stream, Token, TokenKind};
+pub(crate) use token::concat_strings;
@@ -1,8 +1,26 @@
-use std::{cell::Cell, cmp::min, error::Error as StdError, fmt, result::Result as StdResult, str};
+use crate::diagnostic::Diagnostic;
+use std::{
+    cell::Cell,
+    cmp::min,
+    error::Error as StdError,
+    fmt,
+    rc::Rc,
+    result::Result as StdResult,
+    str,
+};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub enum ErrorKind {
     InvalidChar(char),
+    UnterminatedString,
+    UnterminatedChar,
+    InvalidEscape(char),
+    EscapeOutOfRange,    // escape value does not fit in the element type
+    EmptyChar,           // ''
+    MultiChar,           // prefixed char literal with more than one character
+    IncompatibleStrings, // adjacent literals with different prefixes
+    UnterminatedComment,
+    NumberTooLarge, // integer constant which does not fit in 64 bits
     Eof,
 }
 
@@ -16,22 +34,37 @@ impl Error {
     fn eof(loc: Loc) -> Self {
         Error::new(ErrorKind::Eof, loc)
     }
+    pub(crate) fn diagnostic(&self) -> Diagnostic {
+        Diagnostic::error(self.to_string()).at(self.loc.clone())
+    }
 }
 
-impl fmt::Display for Error {
+impl fmt::Display for ErrorKind {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use ErrorKind::*;
-        match self.value {
-            InvalidChar(c) => {
-                let padd = " ".repeat(self.loc.0);
-                let allow = "^".repeat(self.loc.1 - self.loc.0);
-                write!(f, "{}{} invalid char '{}'", padd, allow, c)
-            }
-            _ => write!(f, "lex error"),
+        match *self {
+            InvalidChar(c) => write!(f, "invalid char '{}'", c),
+            UnterminatedString => write!(f, "missing terminating '\"' character"),
+            UnterminatedChar => write!(f, "missing terminating ' character"),
+            InvalidEscape(c) => write!(f, "unknown escape sequence '\\{}'", c),
+            EscapeOutOfRange => write!(f, "escape sequence out of range"),
+            EmptyChar => write!(f, "empty character constant"),
+            MultiChar => write!(f, "multi-character constant with an encoding prefix"),
+            IncompatibleStrings => write!(f, "concatenation of incompatible string literals"),
+            UnterminatedComment => write!(f, "unterminated comment"),
+            NumberTooLarge => write!(f, "integer constant is too large for its type"),
+            Eof => write!(f, "unexpected end of input"),
         }
     }
 }
 
+// the message alone, crate::Error::display shows it at its line in the source.
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
 impl StdError for Error {}
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -67,15 +100,93 @@ pub enum TokenKind {
     Ident(Ident), // foo, bar,
     SemiColon,    // ;
     Assign,       // =
+    Question,     // ?
+    Colon,        // :
+    Comma,        // ,
+    LBrace,       // {
+    RBrace,       // }
+    Switch,       // switch
+    Case,         // case
+    Default,      // default
+    Break,        // break
+    Continue,     // continue
+    Goto,         // goto
+    Str(StrLit),  // "foo", L"foo", u8"foo", ...
+    Hash,         // #
+    HashHash,     // ##
+    Dot,          // .
+    Ellipsis,     // ...
+    Not,          // !
+    Tilde,        // ~
+    Percent,      // %
+    Amp,          // &
+    Pipe,         // |
+    Caret,        // ^
+    AndAnd,       // &&
+    OrOr,         // ||
+    Shl,          // <<
+    Shr,          // >>
+    Error(ErrorKind), // what tokenize_deferred could not read, until it is known to matter
     Eof,          // sentinel
 }
 
 impl TokenKind {
     pub(crate) fn is_number(&self) -> bool {
-        match *self {
-            TokenKind::Number(_) => true,
-            _ => false,
-        }
+        matches!(*self, TokenKind::Number(_))
+    }
+}
+
+// how the token is spelled in the source, for messages.
+impl fmt::Display for TokenKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use TokenKind::*;
+        let s = match *self {
+            Number(n) => return write!(f, "{}", n),
+            Ident(ref ident) => return write!(f, "{}", ident.name),
+            Str(_) => "string literal",
+            Plus => "+",
+            Minus => "-",
+            Asterisk => "*",
+            Slash => "/",
+            LParen => "(",
+            RParen => ")",
+            Eq => "==",
+            Ne => "!=",
+            Ge => ">=",
+            Gt => ">",
+            Le => "<=",
+            Lt => "<",
+            SemiColon => ";",
+            Assign => "=",
+            Question => "?",
+            Colon => ":",
+            Comma => ",",
+            LBrace => "{",
+            RBrace => "}",
+            Switch => "switch",
+            Case => "case",
+            Default => "default",
+            Break => "break",
+            Continue => "continue",
+            Goto => "goto",
+            Hash => "#",
+            HashHash => "##",
+            Dot => ".",
+            Ellipsis => "...",
+            Not => "!",
+            Tilde => "~",
+            Percent => "%",
+            Amp => "&",
+            Pipe => "|",
+            Caret => "^",
+            AndAnd => "&&",
+            OrOr => "||",
+            Shl => "<<",
+            Shr => ">>",
+            Error(ref kind) => return write!(f, "{}", kind),
+            Eof => "end of input",
+        };
+        write!(f, "{}", s)
     }
 }
 
@@ -100,9 +211,98 @@ impl Ident {
     }
 }
 
-pub type Token = Annot<TokenKind>;
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    Char,  // "foo"
+    Utf8,  // u8"foo"
+    Utf16, // u"foo"
+    Utf32, // U"foo"
+    Wide,  // L"foo"
+}
+
+impl Encoding {
+    // size in bytes of an element: char, char16_t, char32_t or wchar_t.
+    pub fn elem_size(self) -> usize {
+        match self {
+            Encoding::Char | Encoding::Utf8 => 1,
+            Encoding::Utf16 => 2,
+            Encoding::Utf32 | Encoding::Wide => 4,
+        }
+    }
+    fn max(self) -> u64 {
+        (1 << (self.elem_size() * 8)) - 1
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StrLit {
+    pub encoding: Encoding,
+    pub value: Vec<u32>, // elements without the terminating null
+}
+
+// the literal as it could be written, with escapes for what is not printable ascii.
+impl fmt::Display for StrLit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let prefix = match self.encoding {
+            Encoding::Char => "",
+            Encoding::Utf8 => "u8",
+            Encoding::Utf16 => "u",
+            Encoding::Utf32 => "U",
+            Encoding::Wide => "L",
+        };
+        write!(f, "{}\"", prefix)?;
+        // escapes have a fixed length so a digit after one is not read as part of it.
+        for &c in &self.value {
+            match char::from_u32(c) {
+                Some(c @ ('"' | '\\')) => write!(f, "\\{}", c)?,
+                Some(c) if c == ' ' || c.is_ascii_graphic() => write!(f, "{}", c)?,
+                Some('\n') => write!(f, "\\n")?,
+                Some('\t') => write!(f, "\\t")?,
+                _ if c <= 0o777 => write!(f, "\\{:03o}", c)?,
+                _ if c <= 0xffff => write!(f, "\\u{:04x}", c)?,
+                _ => write!(f, "\\U{:08x}", c)?,
+            }
+        }
+        write!(f, "\"")
+    }
+}
+
+impl StrLit {
+    // appends an adjacent literal. an unprefixed literal takes the prefix of the other one.
+    fn append(&mut self, other: &StrLit) -> bool {
+        if self.encoding == Encoding::Char {
+            self.encoding = other.encoding;
+        } else if other.encoding != Encoding::Char && other.encoding != self.encoding {
+            return false;
+        }
+        self.value.extend_from_slice(&other.value);
+        true
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token {
+    pub value: TokenKind,
+    pub loc: Loc, // where the token is spelled, inside the macro body for expanded tokens
+    pub expansion: Option<Rc<Expansion>>, // innermost macro expansion producing this token
+}
+
+// a macro invocation. expanded tokens keep the chain of these to the source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expansion {
+    pub name: String,
+    pub loc: Loc, // the macro name at the invocation
+    pub parent: Option<Rc<Expansion>>,
+}
 
 impl Token {
+    fn new(value: TokenKind, loc: Loc) -> Self {
+        Self {
+            value,
+            loc,
+            expansion: None,
+        }
+    }
     pub(crate) fn number(n: u64, loc: Loc) -> Self {
         Self::new(TokenKind::Number(n), loc)
     }
@@ -151,6 +351,69 @@ impl Token {
     pub(crate) fn assign(loc: Loc) -> Self {
         Self::new(TokenKind::Assign, loc)
     }
+    pub(crate) fn question(loc: Loc) -> Self {
+        Self::new(TokenKind::Question, loc)
+    }
+    pub(crate) fn colon(loc: Loc) -> Self {
+        Self::new(TokenKind::Colon, loc)
+    }
+    pub(crate) fn comma(loc: Loc) -> Self {
+        Self::new(TokenKind::Comma, loc)
+    }
+    pub(crate) fn lbrace(loc: Loc) -> Self {
+        Self::new(TokenKind::LBrace, loc)
+    }
+    pub(crate) fn rbrace(loc: Loc) -> Self {
+        Self::new(TokenKind::RBrace, loc)
+    }
+    pub(crate) fn str(lit: StrLit, loc: Loc) -> Self {
+        Self::new(TokenKind::Str(lit), loc)
+    }
+    pub(crate) fn hash(loc: Loc) -> Self {
+        Self::new(TokenKind::Hash, loc)
+    }
+    pub(crate) fn hash_hash(loc: Loc) -> Self {
+        Self::new(TokenKind::HashHash, loc)
+    }
+    pub(crate) fn dot(loc: Loc) -> Self {
+        Self::new(TokenKind::Dot, loc)
+    }
+    pub(crate) fn ellipsis(loc: Loc) -> Self {
+        Self::new(TokenKind::Ellipsis, loc)
+    }
+    pub(crate) fn not(loc: Loc) -> Self {
+        Self::new(TokenKind::Not, loc)
+    }
+    pub(crate) fn tilde(loc: Loc) -> Self {
+        Self::new(TokenKind::Tilde, loc)
+    }
+    pub(crate) fn percent(loc: Loc) -> Self {
+        Self::new(TokenKind::Percent, loc)
+    }
+    pub(crate) fn amp(loc: Loc) -> Self {
+        Self::new(TokenKind::Amp, loc)
+    }
+    pub(crate) fn pipe(loc: Loc) -> Self {
+        Self::new(TokenKind::Pipe, loc)
+    }
+    pub(crate) fn caret(loc: Loc) -> Self {
+        Self::new(TokenKind::Caret, loc)
+    }
+    pub(crate) fn and_and(loc: Loc) -> Self {
+        Self::new(TokenKind::AndAnd, loc)
+    }
+    pub(crate) fn or_or(loc: Loc) -> Self {
+        Self::new(TokenKind::OrOr, loc)
+    }
+    pub(crate) fn shl(loc: Loc) -> Self {
+        Self::new(TokenKind::Shl, loc)
+    }
+    pub(crate) fn shr(loc: Loc) -> Self {
+        Self::new(TokenKind::Shr, loc)
+    }
+    pub(crate) fn keyword(kind: TokenKind, loc: Loc) -> Self {
+        Self::new(kind, loc)
+    }
     pub(crate) fn is_kind(&self, kind: TokenKind) -> bool {
         match self.value {
             TokenKind::Number(_) => kind.is_number(),
@@ -158,9 +421,13 @@ impl Token {
         }
     }
     pub(crate) fn is_ident(&self) -> bool {
+        matches!(self.value, TokenKind::Ident(_))
+    }
+    // the lexical error an Error token stands for.
+    pub(crate) fn error(&self) -> Option<Error> {
         match self.value {
-            TokenKind::Ident(_) => true,
-            _ => false,
+            TokenKind::Error(ref kind) => Some(Error::new(kind.clone(), self.loc.clone())),
+            _ => None,
         }
     }
     fn eof(loc: Loc) -> Self {
@@ -212,15 +479,36 @@ impl<'a> Input<'a> {
         let n = str::from_utf8(&self.input[start..self.pos()])
             .unwrap()
             .parse()
-            .unwrap();
+            .map_err(|_| Error::new(ErrorKind::NumberTooLarge, Loc(start, self.pos())))?;
         Ok((start, n))
     }
+    fn starts_with(&self, want: &[u8]) -> bool {
+        self.input[self.pos()..].starts_with(want)
+    }
     fn consume_spaces(&self) {
-        self.consume(|b| b" \n\t".contains(&b))
+        self.consume(|b| b.is_ascii_whitespace())
+    }
+    // skips a // or /* */ comment.
+    fn consume_comment(&self) -> Result<()> {
+        let start = self.pos();
+        if self.consume_bytes(b"//")?.0 {
+            self.consume(|b| b != b'\n');
+            return Ok(());
+        }
+        self.inc_n(2);
+        loop {
+            if self.consume_bytes(b"*/")?.0 {
+                return Ok(());
+            }
+            if self.peek().is_err() {
+                return Err(Error::new(ErrorKind::UnterminatedComment, Loc(start, start + 2)));
+            }
+            self.inc();
+        }
     }
     fn consume_word(&self) -> Result<(usize, &str)> {
         let start = self.pos();
-        self.consume(|b| (b'a' <= b && b <= b'z') || (b'A' <= b && b <= b'Z'));
+        self.consume(|b| b.is_ascii_alphanumeric() || b == b'_');
         Ok((
             start,
             str::from_utf8(&self.input[start..self.pos()]).unwrap(),
@@ -238,9 +526,23 @@ impl<'a> Input<'a> {
     fn peek(&self) -> Result<u8> {
         self.input
             .get(self.pos())
-            .map(|&b| b)
+            .copied()
             .ok_or_else(|| self.eof())
     }
+    // the whole char at the current position, which may take more than one byte.
+    fn peek_char(&self) -> char {
+        let rest = &self.input[self.pos()..];
+        let width = match rest.first() {
+            Some(0xc0..=0xdf) => 2,
+            Some(0xe0..=0xef) => 3,
+            Some(0xf0..=0xff) => 4,
+            _ => 1,
+        };
+        str::from_utf8(&rest[..min(width, rest.len())])
+            .ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or(char::REPLACEMENT_CHARACTER)
+    }
     fn pos(&self) -> usize {
         self.pos.get()
     }
@@ -262,46 +564,131 @@ impl<'a> Input<'a> {
 }
 
 pub fn tokenize(input: &str) -> StdResult<Stream, crate::Error> {
-    let mut tokens = Vec::new();
-    let input = Input::new(input);
+    let mut error = None;
+    let tokens = lex(input, &mut |e| {
+        error = Some(e);
+        false
+    });
+    match error {
+        Some(e) => Err(e.into()),
+        None => Ok(tokens),
+    }
+}
 
-    macro_rules! push {
-        ($lexer:expr) => {{
-            let tk = $lexer?;
-            tokens.push(tk);
-        }};
+// tokenizes input whose first byte is at base, the offset of its file in a SourceMap, going
+// on after each error. an error is kept in the stream as an Error token where it is, the
+// preprocessor reports it unless its line is in a skipped group.
+pub fn tokenize_deferred(input: &str, base: usize) -> Stream {
+    let mut errors = Vec::new();
+    let mut tokens = lex(input, &mut |e| {
+        errors.push(Token::new(TokenKind::Error(e.value), e.loc));
+        true
+    });
+    // each error among the tokens by where it is.
+    for error in errors {
+        let i = tokens.partition_point(|token| token.loc.0 < error.loc.0);
+        tokens.insert(i, error);
+    }
+    for token in &mut tokens {
+        token.loc = Loc(token.loc.0 + base, token.loc.1 + base);
     }
+    tokens
+}
+
+// the tokens of input up to eof. errors go to report, which tells whether to go on.
+fn lex(input: &str, report: &mut dyn FnMut(Error) -> bool) -> Stream {
+    let mut tokens = Vec::new();
+    let input = Input::new(input);
     loop {
-        match input.peek() {
-            Err(e) => match e.value {
-                ErrorKind::Eof => {
-                    tokens.push(Token::eof(e.loc));
-                    return Ok(tokens);
-                }
-                _ => return Err(e.into()),
-            },
-            Ok(b) => match b {
-                b'0'..=b'9' => push!(lex_number(&input)),
-                b'+' => push!(lex_plus(&input)),
-                b'-' => push!(lex_minus(&input)),
-                b'*' => push!(lex_asterisk(&input)),
-                b'/' => push!(lex_slash(&input)),
-                b'(' => push!(lex_lparen(&input)),
-                b')' => push!(lex_rparen(&input)),
-                b'=' => push!(lex_equal(&input)),
-                b'!' => push!(lex_exclamation(&input)),
-                b'>' => push!(lex_greater(&input)),
-                b'<' => push!(lex_less(&input)),
-                b'a'..=b'z' => push!(lex_ident(&input)),
-                b';' => push!(lex_semi_colon(&input)),
-                _ if (b as char).is_ascii_whitespace() => input.consume_spaces(),
-                _ => {
-                    return Err(
-                        Error::invalid_char(b as char, Loc(input.pos(), input.pos() + 1)).into(),
-                    )
+        match next_token(&input) {
+            Ok(Some(token)) if token.is_kind(TokenKind::Eof) => {
+                tokens.push(token);
+                return tokens;
+            }
+            Ok(Some(token)) => tokens.push(token),
+            Ok(None) => (),
+            Err(e) => {
+                let resume = resume(&input, &e);
+                if !report(e) {
+                    let end = input.input.len();
+                    tokens.push(Token::eof(Loc(end, end)));
+                    return tokens;
                 }
-            },
+                input.pos.set(resume);
+            }
+        }
+    }
+}
+
+// the token at the current position, none for spaces, comments and splices.
+fn next_token(input: &Input) -> Result<Option<Token>> {
+    let b = match input.peek() {
+        Ok(b) => b,
+        Err(e) => match e.value {
+            ErrorKind::Eof => return Ok(Some(Token::eof(e.loc))),
+            _ => return Err(e),
+        },
+    };
+    let token = match b {
+        b'"' | b'\'' | b'L' | b'u' | b'U' if literal_prefix(input).is_some() => lex_literal(input),
+        b'0'..=b'9' => lex_number(input),
+        b'+' => lex_plus(input),
+        b'-' => lex_minus(input),
+        b'*' => lex_asterisk(input),
+        b'/' if input.starts_with(b"//") || input.starts_with(b"/*") => {
+            input.consume_comment()?;
+            return Ok(None);
+        }
+        b'/' => lex_slash(input),
+        b'(' => lex_lparen(input),
+        b')' => lex_rparen(input),
+        b'=' => lex_equal(input),
+        b'!' => lex_exclamation(input),
+        b'>' => lex_greater(input),
+        b'<' => lex_less(input),
+        b'a'..=b'z' | b'A'..=b'Z' | b'_' => lex_ident(input),
+        b'#' => lex_hash(input),
+        b'.' => lex_dot(input),
+        b';' => lex_semi_colon(input),
+        b'?' => lex_question(input),
+        b':' => lex_colon(input),
+        b',' => lex_comma(input),
+        b'{' => lex_lbrace(input),
+        b'}' => lex_rbrace(input),
+        b'~' => lex_tilde(input),
+        b'%' => lex_percent(input),
+        b'&' => lex_ampersand(input),
+        b'|' => lex_pipe(input),
+        b'^' => lex_caret(input),
+        _ if (b as char).is_ascii_whitespace() => {
+            input.consume_spaces();
+            return Ok(None);
+        }
+        // a backslash-newline splices two physical lines.
+        b'\\' if input.starts_with(b"\\\n") => {
+            input.inc_n(2);
+            return Ok(None);
+        }
+        _ => {
+            let c = input.peek_char();
+            Err(Error::invalid_char(c, Loc(input.pos(), input.pos() + c.len_utf8())))
         }
+    };
+    token.map(Some)
+}
+
+// where lexing goes on after e: past an invalid char or number, past the line of a broken
+// literal, or at the end of input for a comment which never ends.
+fn resume(input: &Input, e: &Error) -> usize {
+    let end = input.input.len();
+    match e.value {
+        ErrorKind::InvalidChar(_) | ErrorKind::NumberTooLarge => e.loc.1,
+        ErrorKind::UnterminatedComment => end,
+        _ => input.input[e.loc.0..]
+            .iter()
+            .position(|&b| b == b'\n')
+            .map_or(end, |i| e.loc.0 + i)
+            .max(e.loc.0 + 1),
     }
 }
 
@@ -348,7 +735,7 @@ fn lex_rparen(input: &Input) -> Result<Token> {
 }
 
 fn lex_equal(input: &Input) -> Result<Token> {
-    let (consumed, pos) = input.consume_bytes(&[b'=', b'='])?;
+    let (consumed, pos) = input.consume_bytes(b"==")?;
     if consumed {
         Ok(Token::equal(Loc(pos, pos + 2)))
     } else {
@@ -359,18 +746,23 @@ fn lex_equal(input: &Input) -> Result<Token> {
 }
 
 fn lex_exclamation(input: &Input) -> Result<Token> {
-    let (consumed, pos) = input.consume_bytes(&[b'!', b'='])?;
+    let (consumed, pos) = input.consume_bytes(b"!=")?;
     if consumed {
         Ok(Token::not_equal(Loc(pos, pos + 2)))
     } else {
-        Err(Error::invalid_char('!', Loc(pos, pos + 1)))
+        input
+            .consume_byte(b'!')
+            .map(|pos| Token::not(Loc(pos, pos + 1)))
     }
 }
 
 fn lex_greater(input: &Input) -> Result<Token> {
-    let (consumed, pos) = input.consume_bytes(&[b'>', b'='])?;
+    if let (true, pos) = input.consume_bytes(b">>")? {
+        return Ok(Token::shr(Loc(pos, pos + 2)));
+    }
+    let (consumed, pos) = input.consume_bytes(b">=")?;
     if consumed {
-        return Ok(Token::greater_equal(Loc(pos, pos + 2)));
+        Ok(Token::greater_equal(Loc(pos, pos + 2)))
     } else {
         input
             .consume_byte(b'>')
@@ -379,9 +771,12 @@ fn lex_greater(input: &Input) -> Result<Token> {
 }
 
 fn lex_less(input: &Input) -> Result<Token> {
-    let (consumed, pos) = input.consume_bytes(&[b'<', b'='])?;
+    if let (true, pos) = input.consume_bytes(b"<<")? {
+        return Ok(Token::shl(Loc(pos, pos + 2)));
+    }
+    let (consumed, pos) = input.consume_bytes(b"<=")?;
     if consumed {
-        return Ok(Token::less_equal(Loc(pos, pos + 2)));
+        Ok(Token::less_equal(Loc(pos, pos + 2)))
     } else {
         input
             .consume_byte(b'<')
@@ -390,9 +785,18 @@ fn lex_less(input: &Input) -> Result<Token> {
 }
 
 fn lex_ident(input: &Input) -> Result<Token> {
-    input
-        .consume_word()
-        .map(|(pos, s)| Token::ident(s, Loc(pos, pos + s.len())))
+    input.consume_word().map(|(pos, s)| {
+        let loc = Loc(pos, pos + s.len());
+        match s {
+            "switch" => Token::keyword(TokenKind::Switch, loc),
+            "case" => Token::keyword(TokenKind::Case, loc),
+            "default" => Token::keyword(TokenKind::Default, loc),
+            "break" => Token::keyword(TokenKind::Break, loc),
+            "continue" => Token::keyword(TokenKind::Continue, loc),
+            "goto" => Token::keyword(TokenKind::Goto, loc),
+            _ => Token::ident(s, loc),
+        }
+    })
 }
 
 fn lex_semi_colon(input: &Input) -> Result<Token> {
@@ -400,6 +804,272 @@ fn lex_semi_colon(input: &Input) -> Result<Token> {
         .map(|pos| Token::semi_colon(Loc(pos, pos+1)))
 }
 
+fn lex_question(input: &Input) -> Result<Token> {
+    input
+        .consume_byte(b'?')
+        .map(|pos| Token::question(Loc(pos, pos + 1)))
+}
+
+fn lex_colon(input: &Input) -> Result<Token> {
+    input
+        .consume_byte(b':')
+        .map(|pos| Token::colon(Loc(pos, pos + 1)))
+}
+
+fn lex_comma(input: &Input) -> Result<Token> {
+    input
+        .consume_byte(b',')
+        .map(|pos| Token::comma(Loc(pos, pos + 1)))
+}
+
+fn lex_hash(input: &Input) -> Result<Token> {
+    let (consumed, pos) = input.consume_bytes(b"##")?;
+    if consumed {
+        Ok(Token::hash_hash(Loc(pos, pos + 2)))
+    } else {
+        input
+            .consume_byte(b'#')
+            .map(|pos| Token::hash(Loc(pos, pos + 1)))
+    }
+}
+
+fn lex_dot(input: &Input) -> Result<Token> {
+    let (consumed, pos) = input.consume_bytes(b"...")?;
+    if consumed {
+        Ok(Token::ellipsis(Loc(pos, pos + 3)))
+    } else {
+        input
+            .consume_byte(b'.')
+            .map(|pos| Token::dot(Loc(pos, pos + 1)))
+    }
+}
+
+fn lex_lbrace(input: &Input) -> Result<Token> {
+    input
+        .consume_byte(b'{')
+        .map(|pos| Token::lbrace(Loc(pos, pos + 1)))
+}
+
+fn lex_rbrace(input: &Input) -> Result<Token> {
+    input
+        .consume_byte(b'}')
+        .map(|pos| Token::rbrace(Loc(pos, pos + 1)))
+}
+
+fn lex_tilde(input: &Input) -> Result<Token> {
+    input
+        .consume_byte(b'~')
+        .map(|pos| Token::tilde(Loc(pos, pos + 1)))
+}
+
+fn lex_percent(input: &Input) -> Result<Token> {
+    input
+        .consume_byte(b'%')
+        .map(|pos| Token::percent(Loc(pos, pos + 1)))
+}
+
+fn lex_ampersand(input: &Input) -> Result<Token> {
+    let (consumed, pos) = input.consume_bytes(b"&&")?;
+    if consumed {
+        Ok(Token::and_and(Loc(pos, pos + 2)))
+    } else {
+        input
+            .consume_byte(b'&')
+            .map(|pos| Token::amp(Loc(pos, pos + 1)))
+    }
+}
+
+fn lex_pipe(input: &Input) -> Result<Token> {
+    let (consumed, pos) = input.consume_bytes(b"||")?;
+    if consumed {
+        Ok(Token::or_or(Loc(pos, pos + 2)))
+    } else {
+        input
+            .consume_byte(b'|')
+            .map(|pos| Token::pipe(Loc(pos, pos + 1)))
+    }
+}
+
+fn lex_caret(input: &Input) -> Result<Token> {
+    input
+        .consume_byte(b'^')
+        .map(|pos| Token::caret(Loc(pos, pos + 1)))
+}
+
+const LITERAL_PREFIXES: [(&[u8], Encoding); 5] = [
+    (b"u8", Encoding::Utf8),
+    (b"u", Encoding::Utf16),
+    (b"U", Encoding::Utf32),
+    (b"L", Encoding::Wide),
+    (b"", Encoding::Char),
+];
+
+// returns the encoding and prefix length if a string or char literal starts here.
+fn literal_prefix(input: &Input) -> Option<(Encoding, usize)> {
+    LITERAL_PREFIXES.iter().find_map(|&(prefix, encoding)| {
+        let quoted = input.starts_with(prefix)
+            && matches!(input.input.get(input.pos() + prefix.len()), Some(b'"' | b'\''));
+        if quoted {
+            Some((encoding, prefix.len()))
+        } else {
+            None
+        }
+    })
+}
+
+fn lex_literal(input: &Input) -> Result<Token> {
+    let start = input.pos();
+    let (encoding, prefix) = literal_prefix(input).unwrap();
+    input.inc_n(prefix);
+    let quote = input.peek()?;
+    input.inc();
+
+    let mut value = Vec::new();
+    loop {
+        let pos = input.pos();
+        match input.peek() {
+            Ok(b) if b == quote => {
+                input.inc();
+                break;
+            }
+            Ok(b'\\') => {
+                input.inc();
+                value.push(lex_escape(input, encoding)?);
+            }
+            Ok(b'\n') | Err(_) => {
+                let kind = if quote == b'"' {
+                    ErrorKind::UnterminatedString
+                } else {
+                    ErrorKind::UnterminatedChar
+                };
+                return Err(Error::new(kind, Loc(start, pos)));
+            }
+            Ok(_) => lex_source_char(input, encoding, &mut value),
+        }
+    }
+
+    let loc = Loc(start, input.pos());
+    if quote == b'"' {
+        return Ok(Token::str(StrLit { encoding, value }, loc));
+    }
+    match (encoding, value.as_slice()) {
+        (_, []) => Err(Error::new(ErrorKind::EmptyChar, loc)),
+        (_, &[c]) => Ok(Token::number(c.into(), loc)),
+        // 'ab' is an int made of the chars in big endian order, like gcc does.
+        (Encoding::Char, chars) => {
+            let n = chars.iter().fold(0u64, |n, &c| (n << 8 | u64::from(c)) & 0xffff_ffff);
+            Ok(Token::number(n, loc))
+        }
+        _ => Err(Error::new(ErrorKind::MultiChar, loc)),
+    }
+}
+
+// a character written as is in the source, narrow literals keep its utf-8 bytes.
+fn lex_source_char(input: &Input, encoding: Encoding, value: &mut Vec<u32>) {
+    let pos = input.pos();
+    if encoding.elem_size() == 1 {
+        value.push(input.input[pos].into());
+        input.inc();
+        return;
+    }
+    let len = match input.input[pos] {
+        0x00..=0x7f => 1,
+        0x80..=0xdf => 2,
+        0xe0..=0xef => 3,
+        _ => 4,
+    };
+    let c = str::from_utf8(&input.input[pos..pos + len])
+        .ok()
+        .and_then(|s| s.chars().next())
+        .unwrap_or(char::REPLACEMENT_CHARACTER);
+    input.inc_n(len);
+    if encoding == Encoding::Utf16 {
+        value.extend(c.encode_utf16(&mut [0; 2]).iter().map(|&u| u32::from(u)));
+    } else {
+        value.push(c.into());
+    }
+}
+
+// input is just after the backslash.
+fn lex_escape(input: &Input, encoding: Encoding) -> Result<u32> {
+    let start = input.pos() - 1;
+    let b = input.peek()?;
+    let n: u64 = match b {
+        b'0'..=b'7' => {
+            let mut n = 0;
+            for _ in 0..3 {
+                match input.peek() {
+                    Ok(b @ b'0'..=b'7') => n = n * 8 + u64::from(b - b'0'),
+                    _ => break,
+                }
+                input.inc();
+            }
+            n
+        }
+        b'x' => {
+            input.inc();
+            let digits = input.pos();
+            let mut n: u64 = 0;
+            while let Some(d) = input.peek().ok().and_then(|b| (b as char).to_digit(16)) {
+                n = n.saturating_mul(16).saturating_add(d.into());
+                input.inc();
+            }
+            if input.pos() == digits {
+                return Err(Error::new(ErrorKind::InvalidEscape('x'), Loc(start, input.pos())));
+            }
+            n
+        }
+        _ => {
+            input.inc();
+            match b {
+                b'n' => b'\n'.into(),
+                b't' => b'\t'.into(),
+                b'r' => b'\r'.into(),
+                b'a' => 0x07,
+                b'b' => 0x08,
+                b'f' => 0x0c,
+                b'v' => 0x0b,
+                b'e' => 0x1b, // gnu extension
+                b'\\' | b'\'' | b'"' | b'?' => b.into(),
+                _ => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidEscape(b as char),
+                        Loc(start, input.pos()),
+                    ))
+                }
+            }
+        }
+    };
+    if n > encoding.max() {
+        return Err(Error::new(ErrorKind::EscapeOutOfRange, Loc(start, input.pos())));
+    }
+    Ok(n as u32)
+}
+
+// joins adjacent string literals into one token. this runs after preprocessing.
+pub(crate) fn concat_strings(stream: Stream) -> Result<Stream> {
+    let mut tokens: Stream = Vec::with_capacity(stream.len());
+    for token in stream {
+        if let (
+            TokenKind::Str(next),
+            Some(Token {
+                value: TokenKind::Str(prev),
+                loc,
+                ..
+            }),
+        ) = (&token.value, tokens.last_mut())
+        {
+            if !prev.append(next) {
+                return Err(Error::new(ErrorKind::IncompatibleStrings, token.loc));
+            }
+            loc.1 = token.loc.1;
+            continue;
+        }
+        tokens.push(token);
+    }
+    Ok(tokens)
+}
+
 #[cfg(test)]
 #[path = "./token_test.rs"]
 mod token_test;
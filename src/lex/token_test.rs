@@ -1,4 +1,5 @@
 use super::*;
+use crate::SourceMap;
 
 #[test]
 fn tokenize_test() {
@@ -38,6 +39,12 @@ fn tokenize_test() {
             Token::eof(Loc(17, 17)),
         ]
     );
+
+    assert_eq!(tokenize("18446744073709551615").unwrap()[0], Token::number(u64::MAX, Loc(0, 20)));
+    assert_eq!(
+        lex_error("1 + 18446744073709551616;"),
+        "    ^^^^^^^^^^^^^^^^^^^^ integer constant is too large for its type",
+    );
 }
 
 #[test]
@@ -112,8 +119,259 @@ fn assign_test() {
     );
 }
 
+#[test]
+fn conditional_and_comma_test() {
+    assert_eq!(
+        tokenize("a?1:2,b").unwrap(),
+        tokens(vec![
+            Token::ident("a", Loc(0, 1)),
+            Token::question(Loc(1, 2)),
+            Token::number(1, Loc(2, 3)),
+            Token::colon(Loc(3, 4)),
+            Token::number(2, Loc(4, 5)),
+            Token::comma(Loc(5, 6)),
+            Token::ident("b", Loc(6, 7)),
+        ]),
+    );
+}
+
+#[test]
+fn keyword_test() {
+    assert_eq!(
+        tokenize("switch case default break continue goto cases").unwrap(),
+        tokens(vec![
+            Token::keyword(TokenKind::Switch, Loc(0, 6)),
+            Token::keyword(TokenKind::Case, Loc(7, 11)),
+            Token::keyword(TokenKind::Default, Loc(12, 19)),
+            Token::keyword(TokenKind::Break, Loc(20, 25)),
+            Token::keyword(TokenKind::Continue, Loc(26, 34)),
+            Token::keyword(TokenKind::Goto, Loc(35, 39)),
+            Token::ident("cases", Loc(40, 45)),
+        ]),
+    );
+    assert_eq!(
+        tokenize("{}").unwrap(),
+        tokens(vec![Token::lbrace(Loc(0, 1)), Token::rbrace(Loc(1, 2))]),
+    );
+}
+
+#[test]
+fn string_literal_test() {
+    let narrow = |s: &str| StrLit {
+        encoding: Encoding::Char,
+        value: s.bytes().map(u32::from).collect(),
+    };
+    assert_eq!(
+        tokenize(r#""a\n\t\\\"\101\x42\0""#).unwrap(),
+        tokens(vec![Token::str(
+            StrLit {
+                encoding: Encoding::Char,
+                value: vec![97, 10, 9, 92, 34, 65, 66, 0],
+            },
+            Loc(0, 21)
+        )]),
+    );
+    assert_eq!(
+        joined(r#""ab" "c"  "d""#).unwrap(),
+        tokens(vec![Token::str(narrow("abcd"), Loc(0, 13))]),
+    );
+    assert_eq!(
+        tokenize("\"é\"").unwrap(),
+        tokens(vec![Token::str(narrow("é"), Loc(0, 4))]),
+    );
+}
+
+#[test]
+fn prefixed_string_literal_test() {
+    let lit = |encoding, value: &[u32]| StrLit {
+        encoding,
+        value: value.to_vec(),
+    };
+    assert_eq!(
+        tokenize(r#"u8"é""#).unwrap(),
+        tokens(vec![Token::str(lit(Encoding::Utf8, &[0xc3, 0xa9]), Loc(0, 6))]),
+    );
+    assert_eq!(
+        tokenize(r#"u"é😀""#).unwrap(),
+        tokens(vec![Token::str(lit(Encoding::Utf16, &[0xe9, 0xd83d, 0xde00]), Loc(0, 9))]),
+    );
+    assert_eq!(
+        tokenize(r#"U"😀""#).unwrap(),
+        tokens(vec![Token::str(lit(Encoding::Utf32, &[0x1f600]), Loc(0, 7))]),
+    );
+    assert_eq!(
+        tokenize(r#"L"\x1F600""#).unwrap(),
+        tokens(vec![Token::str(lit(Encoding::Wide, &[0x1f600]), Loc(0, 10))]),
+    );
+    // an unprefixed literal takes the prefix of the adjacent one.
+    assert_eq!(
+        joined(r#""a" L"b""#).unwrap(),
+        tokens(vec![Token::str(lit(Encoding::Wide, &[97, 98]), Loc(0, 8))]),
+    );
+    assert_eq!(
+        shown(r#"u"a" U"b""#, joined(r#"u"a" U"b""#).unwrap_err().into()),
+        "     ^^^^ concatenation of incompatible string literals",
+    );
+    assert_eq!(
+        lex_error(r#""\x100""#),
+        " ^^^^^ escape sequence out of range",
+    );
+    assert_eq!(
+        tokenize(r#"u"\x100""#).unwrap(),
+        tokens(vec![Token::str(lit(Encoding::Utf16, &[0x100]), Loc(0, 8))]),
+    );
+
+    // shown as written, an escape is not continued by the digit after it.
+    assert_eq!(lit(Encoding::Char, &[97, 34, 92, 10, 1, 49]).to_string(), r#""a\"\\\n\0011""#);
+    assert_eq!(lit(Encoding::Wide, &[0xe9, 0x1f600]).to_string(), r#"L"\351\U0001f600""#);
+}
+
+#[test]
+fn char_literal_test() {
+    assert_eq!(
+        tokenize(r"'a' '\n' '\'' L'\x41' u'é' 'ab'").unwrap(),
+        tokens(vec![
+            Token::number(97, Loc(0, 3)),
+            Token::number(10, Loc(4, 8)),
+            Token::number(39, Loc(9, 13)),
+            Token::number(65, Loc(14, 21)),
+            Token::number(0xe9, Loc(22, 27)),
+            Token::number(0x6162, Loc(28, 32)),
+        ]),
+    );
+    assert_eq!(
+        lex_error("''"),
+        "^^ empty character constant",
+    );
+    assert_eq!(
+        lex_error("'a"),
+        "^^ missing terminating ' character",
+    );
+    assert_eq!(
+        lex_error(r"'\q'"),
+        " ^^ unknown escape sequence '\\q'",
+    );
+}
+
+#[test]
+fn preprocessor_token_test() {
+    assert_eq!(
+        tokenize("# ## . _Foo_1 ....").unwrap(),
+        tokens(vec![
+            Token::hash(Loc(0, 1)),
+            Token::hash_hash(Loc(2, 4)),
+            Token::dot(Loc(5, 6)),
+            Token::ident("_Foo_1", Loc(7, 13)),
+            Token::ellipsis(Loc(14, 17)),
+            Token::dot(Loc(17, 18)),
+        ]),
+    );
+}
+
+#[test]
+fn operator_test() {
+    assert_eq!(
+        tokenize("! ~ % & && | || ^ << >> <<= >>=").unwrap(),
+        tokens(vec![
+            Token::not(Loc(0, 1)),
+            Token::tilde(Loc(2, 3)),
+            Token::percent(Loc(4, 5)),
+            Token::amp(Loc(6, 7)),
+            Token::and_and(Loc(8, 10)),
+            Token::pipe(Loc(11, 12)),
+            Token::or_or(Loc(13, 15)),
+            Token::caret(Loc(16, 17)),
+            Token::shl(Loc(18, 20)),
+            Token::shr(Loc(21, 23)),
+            Token::shl(Loc(24, 26)),
+            Token::assign(Loc(26, 27)),
+            Token::shr(Loc(28, 30)),
+            Token::assign(Loc(30, 31)),
+        ]),
+    );
+}
+
+#[test]
+fn comment_test() {
+    assert_eq!(
+        tokenize("1 // 2\n3 /* 4\n */ /5\\\n6").unwrap(),
+        tokens(vec![
+            Token::number(1, Loc(0, 1)),
+            Token::number(3, Loc(7, 8)),
+            Token::slash(Loc(18, 19)),
+            Token::number(5, Loc(19, 20)),
+            Token::number(6, Loc(22, 23)),
+        ]),
+    );
+    assert_eq!(lex_error("1 /* 2"), "  ^^ unterminated comment");
+    // the caret is under the column on the line of the error.
+    assert_eq!(lex_error("1;\n  /* 2"), "  ^^ unterminated comment");
+}
+
 fn tokens(mut v: Vec<Token>) -> Vec<Token> {
     let pos = v.last().unwrap().loc.1;
     v.push(Token::eof(Loc(pos, pos)));
     v
 }
+
+#[test]
+fn tokenize_deferred_test() {
+    // an error is kept where it is, the rest of the line of a broken literal is skipped.
+    let stream = tokenize_deferred("1 @ 2\n\"a\\q\" 3\n4 $", 10);
+    let error = |kind, loc| Token::new(TokenKind::Error(kind), loc);
+    assert_eq!(
+        stream,
+        vec![
+            Token::number(1, Loc(10, 11)),
+            error(ErrorKind::InvalidChar('@'), Loc(12, 13)),
+            Token::number(2, Loc(14, 15)),
+            error(ErrorKind::InvalidEscape('q'), Loc(18, 20)),
+            Token::number(4, Loc(24, 25)),
+            error(ErrorKind::InvalidChar('$'), Loc(26, 27)),
+            Token::eof(Loc(27, 27)),
+        ],
+    );
+    let e = stream[3].error().unwrap();
+    assert_eq!((e.to_string(), e.loc), ("unknown escape sequence '\\q'".to_owned(), Loc(18, 20)));
+
+    // a char which is not ascii is one error, lexing goes on after all of its bytes.
+    let stream = tokenize_deferred("a €1", 0);
+    assert_eq!(
+        stream,
+        vec![
+            Token::ident("a", Loc(0, 1)),
+            error(ErrorKind::InvalidChar('€'), Loc(2, 5)),
+            Token::number(1, Loc(5, 6)),
+            Token::eof(Loc(6, 6)),
+        ],
+    );
+
+    // lexing goes on right after a number too large.
+    let stream = tokenize_deferred("99999999999999999999+1", 0);
+    assert_eq!(
+        stream,
+        vec![
+            error(ErrorKind::NumberTooLarge, Loc(0, 20)),
+            Token::plus(Loc(20, 21)),
+            Token::number(1, Loc(21, 22)),
+            Token::eof(Loc(22, 22)),
+        ],
+    );
+}
+
+fn joined(s: &str) -> Result<Stream> {
+    concat_strings(tokenize(s).unwrap())
+}
+
+fn lex_error(s: &str) -> String {
+    match tokenize(s) {
+        Err(e @ crate::Error::Lexer(_)) => shown(s, e),
+        other => panic!("lexer error expected. got {:?}", other),
+    }
+}
+
+// the caret line of e shown in s, followed by the message.
+fn shown(s: &str, e: crate::Error) -> String {
+    let display = e.display(&SourceMap::new("-", s)).to_string();
+    format!("{} {}", display.lines().last().unwrap(), e)
+}
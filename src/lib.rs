@@ -1,9 +1,17 @@
 mod asm;
 mod ast;
+mod diagnostic;
+mod driver;
 mod error;
 mod lex;
+mod pp;
+mod source;
 
 pub use asm::generate;
 pub use ast::parse;
+pub use diagnostic::{Diagnostic, Diagnostics, Label, Severity};
+pub use driver::run;
 pub use error::Error;
 pub use lex::tokenize;
+pub use pp::{preprocess, preprocess_text, Config as PpConfig, Define};
+pub use source::SourceMap;
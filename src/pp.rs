@@ -0,0 +1,5 @@
+mod expr;
+mod macros;
+mod preprocessor;
+
+pub use preprocessor::{preprocess, preprocess_text, Config, Define, Error};
@@ -0,0 +1,356 @@
+use super::preprocessor::{Error, PpToken, Result};
+use crate::lex::{Token, TokenKind};
+use std::convert::TryFrom;
+
+// evaluates the controlling expression of #if, after defined and macros are replaced.
+// identifiers left are 0 by then, so tokens are numbers and punctuators only.
+pub(super) fn eval(directive: &PpToken, tokens: &[PpToken]) -> Result<bool> {
+    let mut e = Evaluator {
+        directive,
+        tokens,
+        pos: 0,
+        dead: 0,
+    };
+    let v = e.expr()?;
+    match e.peek() {
+        Some(t) => Err(Error::InvalidExpression(t.token.clone()).into()),
+        None => Ok(v.n != 0),
+    }
+}
+
+// preprocessor arithmetic is done in intmax_t or uintmax_t.
+#[derive(Debug, Clone, Copy)]
+struct Value {
+    n: u64, // two's complement bits when signed
+    unsigned: bool,
+}
+
+impl Value {
+    // the int result of logical operators and comparisons.
+    fn bool(b: bool) -> Self {
+        Value {
+            n: b.into(),
+            unsigned: false,
+        }
+    }
+}
+
+struct Evaluator<'a> {
+    directive: &'a PpToken,
+    tokens: &'a [PpToken],
+    pos: usize,
+    dead: usize, // inside an operand which is not evaluated, like the rhs of 0 &&
+}
+
+impl Evaluator<'_> {
+    // expr = logor ("?" expr ":" expr)?
+    fn expr(&mut self) -> Result<Value> {
+        let cond = self.logor()?;
+        if !self.consume(TokenKind::Question) {
+            return Ok(cond);
+        }
+        let then = self.live(cond.n != 0, Self::expr)?;
+        self.expect(TokenKind::Colon)?;
+        let els = self.live(cond.n == 0, Self::expr)?;
+        let n = if cond.n != 0 { then.n } else { els.n };
+        Ok(Value {
+            n,
+            unsigned: then.unsigned || els.unsigned,
+        })
+    }
+
+    // logor = logand ("||" logand)*
+    fn logor(&mut self) -> Result<Value> {
+        let mut v = self.logand()?;
+        while self.consume(TokenKind::OrOr) {
+            let lhs = v.n != 0;
+            let rhs = self.live(!lhs, Self::logand)?;
+            v = Value::bool(lhs || rhs.n != 0);
+        }
+        Ok(v)
+    }
+
+    // logand = bitor ("&&" bitor)*
+    fn logand(&mut self) -> Result<Value> {
+        let mut v = self.bitor()?;
+        while self.consume(TokenKind::AndAnd) {
+            let lhs = v.n != 0;
+            let rhs = self.live(lhs, Self::bitor)?;
+            v = Value::bool(lhs && rhs.n != 0);
+        }
+        Ok(v)
+    }
+
+    // bitor = bitxor ("|" bitxor)*
+    fn bitor(&mut self) -> Result<Value> {
+        let mut v = self.bitxor()?;
+        while self.consume(TokenKind::Pipe) {
+            let rhs = self.bitxor()?;
+            v = arith(v, rhs, |a, b| a | b);
+        }
+        Ok(v)
+    }
+
+    // bitxor = bitand ("^" bitand)*
+    fn bitxor(&mut self) -> Result<Value> {
+        let mut v = self.bitand()?;
+        while self.consume(TokenKind::Caret) {
+            let rhs = self.bitand()?;
+            v = arith(v, rhs, |a, b| a ^ b);
+        }
+        Ok(v)
+    }
+
+    // bitand = equality ("&" equality)*
+    fn bitand(&mut self) -> Result<Value> {
+        let mut v = self.equality()?;
+        while self.consume(TokenKind::Amp) {
+            let rhs = self.equality()?;
+            v = arith(v, rhs, |a, b| a & b);
+        }
+        Ok(v)
+    }
+
+    // equality = relational ("==" relational | "!=" relational)*
+    fn equality(&mut self) -> Result<Value> {
+        let mut v = self.relational()?;
+        loop {
+            if self.consume(TokenKind::Eq) {
+                v = Value::bool(v.n == self.relational()?.n);
+            } else if self.consume(TokenKind::Ne) {
+                v = Value::bool(v.n != self.relational()?.n);
+            } else {
+                return Ok(v);
+            }
+        }
+    }
+
+    // relational = shift ("<" shift | "<=" shift | ">" shift | ">=" shift)*
+    fn relational(&mut self) -> Result<Value> {
+        let mut v = self.shift()?;
+        loop {
+            let op = [
+                TokenKind::Lt,
+                TokenKind::Le,
+                TokenKind::Gt,
+                TokenKind::Ge,
+            ]
+            .iter()
+            .find(|op| self.consume((*op).clone()))
+            .cloned();
+            let op = match op {
+                Some(op) => op,
+                None => return Ok(v),
+            };
+            let rhs = self.shift()?;
+            let ord = if v.unsigned || rhs.unsigned {
+                v.n.cmp(&rhs.n)
+            } else {
+                (v.n as i64).cmp(&(rhs.n as i64))
+            };
+            v = Value::bool(match op {
+                TokenKind::Lt => ord.is_lt(),
+                TokenKind::Le => ord.is_le(),
+                TokenKind::Gt => ord.is_gt(),
+                _ => ord.is_ge(),
+            });
+        }
+    }
+
+    // shift = add ("<<" add | ">>" add)*
+    fn shift(&mut self) -> Result<Value> {
+        let mut v = self.add()?;
+        loop {
+            let left = if self.consume(TokenKind::Shl) {
+                true
+            } else if self.consume(TokenKind::Shr) {
+                false
+            } else {
+                return Ok(v);
+            };
+            // the result has the type of the lhs, too large counts shift everything out.
+            let count = u32::try_from(self.add()?.n).unwrap_or(u32::MAX);
+            v.n = match (left, v.unsigned) {
+                (true, _) => v.n.checked_shl(count).unwrap_or(0),
+                (false, true) => v.n.checked_shr(count).unwrap_or(0),
+                (false, false) => ((v.n as i64) >> count.min(63)) as u64,
+            };
+        }
+    }
+
+    // add = mul ("+" mul | "-" mul)*
+    fn add(&mut self) -> Result<Value> {
+        let mut v = self.mul()?;
+        loop {
+            if self.consume(TokenKind::Plus) {
+                v = arith(v, self.mul()?, u64::wrapping_add);
+            } else if self.consume(TokenKind::Minus) {
+                v = arith(v, self.mul()?, u64::wrapping_sub);
+            } else {
+                return Ok(v);
+            }
+        }
+    }
+
+    // mul = unary ("*" unary | "/" unary | "%" unary)*
+    fn mul(&mut self) -> Result<Value> {
+        let mut v = self.unary()?;
+        loop {
+            if self.consume(TokenKind::Asterisk) {
+                v = arith(v, self.unary()?, u64::wrapping_mul);
+                continue;
+            }
+            let div = if self.consume(TokenKind::Slash) {
+                true
+            } else if self.consume(TokenKind::Percent) {
+                false
+            } else {
+                return Ok(v);
+            };
+            let op = self.tokens[self.pos - 1].token.clone();
+            let rhs = self.unary()?;
+            if rhs.n == 0 {
+                if self.dead > 0 {
+                    continue;
+                }
+                return Err(Error::DivisionByZero(op).into());
+            }
+            v = match (div, v.unsigned || rhs.unsigned) {
+                (true, true) => arith(v, rhs, |a, b| a / b),
+                (false, true) => arith(v, rhs, |a, b| a % b),
+                (true, false) => arith(v, rhs, |a, b| (a as i64).wrapping_div(b as i64) as u64),
+                (false, false) => arith(v, rhs, |a, b| (a as i64).wrapping_rem(b as i64) as u64),
+            };
+        }
+    }
+
+    // unary = ("+" | "-" | "!" | "~") unary | primary
+    fn unary(&mut self) -> Result<Value> {
+        if self.consume(TokenKind::Plus) {
+            self.unary()
+        } else if self.consume(TokenKind::Minus) {
+            let v = self.unary()?;
+            Ok(Value {
+                n: v.n.wrapping_neg(),
+                ..v
+            })
+        } else if self.consume(TokenKind::Not) {
+            Ok(Value::bool(self.unary()?.n == 0))
+        } else if self.consume(TokenKind::Tilde) {
+            let v = self.unary()?;
+            Ok(Value { n: !v.n, ..v })
+        } else {
+            self.primary()
+        }
+    }
+
+    // primary = num | "(" expr ")"
+    fn primary(&mut self) -> Result<Value> {
+        if self.consume(TokenKind::LParen) {
+            let v = self.expr()?;
+            self.expect(TokenKind::RParen)?;
+            return Ok(v);
+        }
+        match self.peek() {
+            Some(t) if t.is(TokenKind::Number(0)) => {
+                let v = constant(t);
+                self.pos += 1;
+                v
+            }
+            _ => Err(self.unexpected()),
+        }
+    }
+
+    // evaluates an operand which only counts if live, errors like division by zero
+    // do not matter in the others.
+    fn live(&mut self, live: bool, f: fn(&mut Self) -> Result<Value>) -> Result<Value> {
+        if live {
+            return f(self);
+        }
+        self.dead += 1;
+        let v = f(self);
+        self.dead -= 1;
+        v
+    }
+
+    fn peek(&self) -> Option<&PpToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn consume(&mut self, kind: TokenKind) -> bool {
+        let found = self.peek().is_some_and(|t| t.is(kind));
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, kind: TokenKind) -> Result<()> {
+        if self.consume(kind) {
+            Ok(())
+        } else {
+            Err(self.unexpected())
+        }
+    }
+
+    // the token which does not fit, or the directive when the line ended.
+    fn unexpected(&self) -> crate::Error {
+        let token: &Token = self.peek().map_or(&self.directive.token, |t| &t.token);
+        Error::InvalidExpression(token.clone()).into()
+    }
+}
+
+// joins each number with the letters and digits right after it, which the lexer reads as an
+// identifier, into the pp-number they spell, like 0x1f or 10UL.
+pub(super) fn join_numbers(tokens: Vec<PpToken>) -> Vec<PpToken> {
+    let mut joined: Vec<PpToken> = Vec::new();
+    for t in tokens {
+        match joined.last_mut() {
+            Some(last) if last.is(TokenKind::Number(0)) && t.token.is_ident() && !t.space => {
+                last.spelling.push_str(&t.spelling);
+                last.token.loc.1 = t.token.loc.1;
+            }
+            _ => joined.push(t),
+        }
+    }
+    joined
+}
+
+// the value of an integer constant, decimal, octal with a leading 0 or hex with 0x, and
+// an optional u and l or ll suffix. character constants are numbers already.
+fn constant(t: &PpToken) -> Result<Value> {
+    let n = match t.token.value {
+        TokenKind::Number(n) if !t.spelling.starts_with(|c: char| c.is_ascii_digit()) => {
+            return Ok(Value { n, unsigned: false })
+        }
+        _ => t.spelling.as_str(),
+    };
+    let invalid = || Error::InvalidNumber(t.token.clone(), t.spelling.clone()).into();
+    let (radix, digits) = match n.get(..2) {
+        Some("0x") | Some("0X") => (16, &n[2..]),
+        _ if n.starts_with('0') => (8, n),
+        _ => (10, n),
+    };
+    let end = digits.find(|c: char| !c.is_digit(radix)).unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(end);
+    let unsigned = match suffix {
+        "" | "l" | "L" | "ll" | "LL" => false,
+        "u" | "U" | "ul" | "uL" | "Ul" | "UL" | "lu" | "lU" | "Lu" | "LU" | "ull" | "uLL"
+        | "Ull" | "ULL" | "llu" | "llU" | "LLu" | "LLU" => true,
+        _ => return Err(invalid()),
+    };
+    let n = u64::from_str_radix(digits, radix).map_err(|_| invalid())?;
+    // a constant too large for intmax_t is unsigned.
+    Ok(Value {
+        n,
+        unsigned: unsigned || n > i64::MAX as u64,
+    })
+}
+
+// applies op to the operands converted to a common type.
+fn arith(a: Value, b: Value, op: impl Fn(u64, u64) -> u64) -> Value {
+    Value {
+        n: op(a.n, b.n),
+        unsigned: a.unsigned || b.unsigned,
+    }
+}
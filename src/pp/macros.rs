@@ -0,0 +1,341 @@
+use super::preprocessor::{Error, PpToken, Result};
+use crate::lex::{self, Encoding, Expansion, StrLit, Token, TokenKind};
+use std::{collections::HashSet, rc::Rc};
+
+const VA_ARGS: &str = "__VA_ARGS__";
+const VA_OPT: &str = "__VA_OPT__";
+
+pub(super) struct Macro {
+    params: Option<Vec<String>>, // None for an object-like macro
+    variadic: bool,
+    body: Vec<PpToken>,
+    builtin: Option<Builtin>,
+}
+
+// object-like macros whose value depends on where they are expanded.
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Builtin {
+    File,
+    Line,
+    Counter,
+}
+
+impl Macro {
+    // parses the arguments of #define into the macro name and its definition.
+    pub(super) fn parse(directive: &PpToken, args: &[PpToken]) -> Result<(String, Macro)> {
+        let name = args
+            .first()
+            .and_then(PpToken::ident)
+            .ok_or_else(|| Error::MacroNameRequired(directive.token.clone()))?;
+        let mut m = Macro {
+            params: None,
+            variadic: false,
+            body: Vec::new(),
+            builtin: None,
+        };
+
+        // only a '(' right after the name starts a parameter list.
+        let mut rest = &args[1..];
+        if rest.first().is_some_and(|t| t.is(TokenKind::LParen) && !t.space) {
+            let (params, variadic, len) = parse_params(rest)?;
+            m.params = Some(params);
+            m.variadic = variadic;
+            rest = &rest[len..];
+        }
+        m.body = rest.to_vec();
+        m.validate()?;
+        Ok((name.to_owned(), m))
+    }
+
+    pub(super) fn builtin(builtin: Builtin) -> Macro {
+        Macro {
+            params: None,
+            variadic: false,
+            body: Vec::new(),
+            builtin: Some(builtin),
+        }
+    }
+
+    pub(super) fn as_builtin(&self) -> Option<Builtin> {
+        self.builtin
+    }
+
+    pub(super) fn is_function(&self) -> bool {
+        self.params.is_some()
+    }
+
+    // number of comma separated arguments, the variadic one takes all the rest.
+    pub(super) fn arg_limit(&self) -> usize {
+        match self.params {
+            Some(ref params) if self.variadic => params.len() + 1,
+            _ => usize::MAX,
+        }
+    }
+
+    // checks the arguments of an invocation against the parameters.
+    // a variadic macro gets its variable arguments as one more argument.
+    pub(super) fn arguments(
+        &self,
+        name: &PpToken,
+        mut args: Vec<Vec<PpToken>>,
+    ) -> Result<Vec<Vec<PpToken>>> {
+        let n = self.params.as_ref().map_or(0, Vec::len);
+        // F() passes one empty argument, which is no argument for F without parameters.
+        if n == 0 && !self.variadic && args.len() == 1 && args[0].is_empty() {
+            args.clear();
+        }
+        if self.variadic && args.len() == n {
+            args.push(Vec::new());
+        }
+        let expected = if self.variadic { n + 1 } else { n };
+        if args.len() != expected {
+            return Err(Error::ArgCount(name.token.clone(), args.len(), n).into());
+        }
+        Ok(args)
+    }
+
+    // replaces the parameters in the body with args. expand fully macro expands an argument.
+    pub(super) fn substitute(
+        &self,
+        args: &[Vec<PpToken>],
+        expansion: &Rc<Expansion>,
+        expand: &mut dyn FnMut(Vec<PpToken>) -> Result<Vec<PpToken>>,
+    ) -> Result<Vec<PpToken>> {
+        Substitution {
+            m: self,
+            args,
+            expansion,
+            expand,
+        }
+        .run(&self.body)
+    }
+
+    // index of the argument t refers to, if it is a parameter.
+    fn param(&self, t: &PpToken) -> Option<usize> {
+        let params = self.params.as_ref()?;
+        let name = t.ident()?;
+        match params.iter().position(|param| param == name) {
+            Some(i) => Some(i),
+            None if self.variadic && name == VA_ARGS => Some(params.len()),
+            None => None,
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        let body = &self.body;
+        if let Some(t) = body.first().filter(|t| t.is(TokenKind::HashHash)) {
+            return Err(Error::PasteAtEdge(t.token.clone()).into());
+        }
+        if let Some(t) = body.last().filter(|t| t.is(TokenKind::HashHash)) {
+            return Err(Error::PasteAtEdge(t.token.clone()).into());
+        }
+        if !self.is_function() {
+            return Ok(());
+        }
+        for (i, t) in body.iter().enumerate() {
+            let next = body.get(i + 1);
+            if t.is(TokenKind::Hash) && next.and_then(|n| self.param(n)).is_none() {
+                return Err(Error::StrayHash(t.token.clone()).into());
+            }
+            if t.ident() == Some(VA_OPT) {
+                let parenthesized = self.variadic
+                    && next.is_some_and(|n| n.is(TokenKind::LParen))
+                    && matching_paren(body, i + 1).is_some();
+                if !parenthesized {
+                    return Err(Error::InvalidVaOpt(t.token.clone()).into());
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// params = "(" (ident ("," ident)* ("," "...")? | "...")? ")"
+// returns the parameter names, whether the macro is variadic and the tokens consumed.
+fn parse_params(tokens: &[PpToken]) -> Result<(Vec<String>, bool, usize)> {
+    let invalid = |i: usize| Error::InvalidParams(tokens[i.min(tokens.len() - 1)].token.clone());
+    let mut params: Vec<String> = Vec::new();
+    let mut i = 1;
+    if tokens.get(i).is_some_and(|t| t.is(TokenKind::RParen)) {
+        return Ok((params, false, i + 1));
+    }
+    loop {
+        let t = tokens.get(i).ok_or_else(|| invalid(i))?;
+        if t.is(TokenKind::Ellipsis) {
+            return match tokens.get(i + 1) {
+                Some(t) if t.is(TokenKind::RParen) => Ok((params, true, i + 2)),
+                _ => Err(invalid(i + 1).into()),
+            };
+        }
+        match t.ident() {
+            Some(name) if name != VA_ARGS && !params.iter().any(|p| p == name) => {
+                params.push(name.to_owned())
+            }
+            _ => return Err(invalid(i).into()),
+        }
+        match tokens.get(i + 1) {
+            Some(t) if t.is(TokenKind::Comma) => i += 2,
+            Some(t) if t.is(TokenKind::RParen) => return Ok((params, false, i + 2)),
+            _ => return Err(invalid(i + 1).into()),
+        }
+    }
+}
+
+// index of the ')' matching the '(' at tokens[open].
+fn matching_paren(tokens: &[PpToken], open: usize) -> Option<usize> {
+    let mut depth = 0;
+    for (i, t) in tokens.iter().enumerate().skip(open) {
+        if t.is(TokenKind::LParen) {
+            depth += 1;
+        } else if t.is(TokenKind::RParen) {
+            depth -= 1;
+            if depth == 0 {
+                return Some(i);
+            }
+        }
+    }
+    None
+}
+
+struct Substitution<'a> {
+    m: &'a Macro,
+    args: &'a [Vec<PpToken>],
+    expansion: &'a Rc<Expansion>,
+    expand: &'a mut dyn FnMut(Vec<PpToken>) -> Result<Vec<PpToken>>,
+}
+
+impl Substitution<'_> {
+    fn run(&mut self, body: &[PpToken]) -> Result<Vec<PpToken>> {
+        let mut out: Vec<PpToken> = Vec::new();
+        // whether the last thing substituted was an empty argument, '##' then has no lhs.
+        let mut placemarker = false;
+        let mut i = 0;
+        while i < body.len() {
+            let t = &body[i];
+            let next = body.get(i + 1);
+
+            // "#" param
+            if t.is(TokenKind::Hash) && self.m.is_function() {
+                let arg = self.m.param(next.unwrap()).unwrap();
+                out.push(stringize(&self.args[arg], self.body_token(t)));
+                placemarker = false;
+                i += 2;
+                continue;
+            }
+
+            // lhs "##" rhs
+            if t.is(TokenKind::HashHash) {
+                let rhs = &body[i + 1];
+                let rhs = match self.m.param(rhs) {
+                    Some(arg) => self.args[arg].clone(),
+                    None => vec![self.body_token(rhs)],
+                };
+                i += 2;
+                if rhs.is_empty() {
+                    continue;
+                }
+                if placemarker {
+                    out.extend(rhs);
+                } else {
+                    let lhs = out.pop().unwrap();
+                    out.push(paste(&lhs, &rhs[0])?);
+                    out.extend(rhs.into_iter().skip(1));
+                }
+                placemarker = false;
+                continue;
+            }
+
+            // an operand of '##' is substituted as written, otherwise it is fully expanded first.
+            if let Some(arg) = self.m.param(t) {
+                let arg = self.args[arg].clone();
+                placemarker = arg.is_empty();
+                if next.is_some_and(|n| n.is(TokenKind::HashHash)) {
+                    out.extend(arg);
+                } else {
+                    out.extend((self.expand)(arg)?);
+                }
+                i += 1;
+                continue;
+            }
+
+            // "__VA_OPT__" "(" token* ")" stands for its content only if there are variable arguments.
+            if self.m.variadic && t.ident() == Some(VA_OPT) {
+                let close = matching_paren(body, i + 1).unwrap();
+                let content = if self.args.last().unwrap().is_empty() {
+                    Vec::new()
+                } else {
+                    self.run(&body[i + 2..close])?
+                };
+                placemarker = content.is_empty();
+                out.extend(content);
+                i = close + 1;
+                continue;
+            }
+
+            out.push(self.body_token(t));
+            placemarker = false;
+            i += 1;
+        }
+        Ok(out)
+    }
+
+    // a token written in the macro body, now coming out of this expansion.
+    fn body_token(&self, t: &PpToken) -> PpToken {
+        let mut t = t.clone();
+        t.token.expansion = Some(self.expansion.clone());
+        t
+    }
+}
+
+// makes a string literal from the spelling of tokens, '#' is the stringizing operator.
+fn stringize(tokens: &[PpToken], hash: PpToken) -> PpToken {
+    let mut text = String::new();
+    for (i, t) in tokens.iter().enumerate() {
+        if i > 0 && t.space {
+            text.push(' ');
+        }
+        text.push_str(&t.spelling);
+    }
+    let mut spelling = String::from("\"");
+    for c in text.chars() {
+        if c == '"' || c == '\\' {
+            spelling.push('\\');
+        }
+        spelling.push(c);
+    }
+    spelling.push('"');
+
+    let lit = StrLit {
+        encoding: Encoding::Char,
+        value: text.bytes().map(u32::from).collect(),
+    };
+    PpToken {
+        token: Token {
+            value: TokenKind::Str(lit),
+            ..hash.token
+        },
+        spelling,
+        ..hash
+    }
+}
+
+// joins two tokens into one, '##' is the token pasting operator.
+fn paste(lhs: &PpToken, rhs: &PpToken) -> Result<PpToken> {
+    let spelling = format!("{}{}", lhs.spelling, rhs.spelling);
+    let invalid = || Error::InvalidPaste(lhs.token.clone(), spelling.clone());
+    let mut stream = lex::tokenize(&spelling).map_err(|_| invalid())?;
+    // the pasted token and eof
+    if stream.len() != 2 {
+        return Err(invalid().into());
+    }
+    let hideset: HashSet<String> = lhs.hideset.union(&rhs.hideset).cloned().collect();
+    Ok(PpToken {
+        token: Token {
+            value: stream.swap_remove(0).value,
+            ..lhs.token.clone()
+        },
+        spelling,
+        hideset,
+        ..lhs.clone()
+    })
+}
@@ -0,0 +1,885 @@
+use super::{
+    expr,
+    macros::{Builtin, Macro},
+};
+use crate::{
+    diagnostic::{site, Diagnostic, Diagnostics},
+    lex::{self, token::Loc, Encoding, Expansion, StrLit, Stream, Token, TokenKind},
+    source::SourceMap,
+};
+use std::{
+    cell::Cell,
+    collections::{HashMap, HashSet, VecDeque},
+    error::Error as StdError,
+    env, fmt, fs, io, mem,
+    path::{Path, PathBuf},
+    rc::Rc,
+    result::Result as StdResult,
+    slice,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// same limit as gcc, deep enough for any sane header and stops self inclusion.
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+// object-like macros defined before the first line, besides __DATE__ and __TIME__.
+const PREDEFINED: [(&str, &str); 5] = [
+    ("__STDC__", "1"),
+    ("__STDC_HOSTED__", "1"),
+    ("__STDC_VERSION__", "201112"),
+    ("__x86_64__", "1"),
+    ("__linux__", "1"),
+];
+
+#[derive(Debug)]
+pub enum Error {
+    UnknownDirective(Token),
+    MacroNameRequired(Token),       // #define or #undef without an identifier
+    InvalidInclude(Token),          // #include without "file" or <file>
+    IncludeNotFound(Token, String), // file name as written
+    IncludeTooDeep(Token),
+    Read(PathBuf, io::Error),
+    InvalidParams(Token),         // malformed parameter list of a function-like macro
+    StrayHash(Token),             // '#' not followed by a parameter in a function-like macro
+    PasteAtEdge(Token),           // '##' at either end of a replacement list
+    InvalidVaOpt(Token),          // __VA_OPT__ outside a variadic macro or without its '(' ')'
+    UnterminatedCall(Token),      // macro invocation without the closing ')'
+    ArgCount(Token, usize, usize), // given and expected number of arguments
+    InvalidPaste(Token, String),  // '##' result which is not a single token
+    UnterminatedConditional(Token), // #if, #ifdef or #ifndef without #endif in the same file
+    StrayConditional(Token),        // #elif, #else or #endif without #if
+    ElseAfterElse(Token),           // #elif or #else after #else
+    InvalidExpression(Token),       // malformed #if expression, at the token or the directive
+    DivisionByZero(Token),          // '/' or '%' in #if
+    InvalidNumber(Token, String),   // integer constant in #if as written
+    Reported(Token, String),        // #error with its message
+    InvalidLine(Token),             // #line without a line number or with a bad file name
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Error::*;
+        match self {
+            UnknownDirective(_) => write!(f, "invalid preprocessing directive"),
+            MacroNameRequired(_) => write!(f, "macro name must be an identifier"),
+            InvalidInclude(_) => write!(f, "#include expects \"FILENAME\" or <FILENAME>"),
+            IncludeNotFound(_, name) => write!(f, "{}: no such file", name),
+            IncludeTooDeep(_) => write!(f, "#include nested depth {} exceeded", MAX_INCLUDE_DEPTH),
+            Read(path, e) => write!(f, "{}: {}", path.display(), e),
+            InvalidParams(_) => write!(f, "invalid macro parameter list"),
+            StrayHash(_) => write!(f, "'#' is not followed by a macro parameter"),
+            PasteAtEdge(_) => write!(f, "'##' cannot appear at either end of a macro expansion"),
+            InvalidVaOpt(_) => write!(f, "__VA_OPT__ must be followed by '(' ... ')' in a variadic macro"),
+            UnterminatedCall(_) => write!(f, "unterminated argument list invoking macro"),
+            ArgCount(_, given, expected) => write!(
+                f,
+                "macro given {} arguments, but takes {}",
+                given, expected
+            ),
+            InvalidPaste(_, text) => write!(
+                f,
+                "pasting forms '{}', an invalid preprocessing token",
+                text
+            ),
+            UnterminatedConditional(t) => write!(f, "unterminated #{}", directive_name(t)),
+            StrayConditional(t) => write!(f, "#{} without #if", directive_name(t)),
+            ElseAfterElse(t) => write!(f, "#{} after #else", directive_name(t)),
+            InvalidExpression(_) => write!(f, "invalid expression in #if"),
+            DivisionByZero(_) => write!(f, "division by zero in #if"),
+            InvalidNumber(_, spelling) => write!(f, "invalid integer constant '{}' in #if", spelling),
+            Reported(_, message) => write!(f, "#error {}", message),
+            InvalidLine(_) => write!(f, "#line expects a line number and an optional \"FILENAME\""),
+        }
+    }
+}
+
+impl Error {
+    // the token the error is about, for its location.
+    pub(crate) fn token(&self) -> Option<&Token> {
+        use Error::*;
+        match self {
+            UnknownDirective(t) | MacroNameRequired(t) | InvalidInclude(t) | IncludeTooDeep(t)
+            | InvalidParams(t) | StrayHash(t) | PasteAtEdge(t) | InvalidVaOpt(t)
+            | UnterminatedCall(t) | UnterminatedConditional(t) | StrayConditional(t)
+            | ElseAfterElse(t) | InvalidExpression(t) | DivisionByZero(t) | InvalidLine(t) => Some(t),
+            IncludeNotFound(t, _) | ArgCount(t, _, _) | InvalidPaste(t, _) | Reported(t, _)
+            | InvalidNumber(t, _) => Some(t),
+            Read(_, _) => None,
+        }
+    }
+
+    pub(crate) fn diagnostic(&self) -> Diagnostic {
+        let d = Diagnostic::error(self.to_string());
+        let d = match self.token() {
+            Some(t) => d.at_token(t),
+            None => d,
+        };
+        match self {
+            Error::IncludeNotFound(..) => {
+                d.help("add the directory containing it to the search path with -I".to_owned())
+            }
+            Error::IncludeTooDeep(_) => {
+                d.note("a header including itself needs #pragma once or an include guard".to_owned())
+            }
+            _ => d,
+        }
+    }
+}
+
+fn directive_name(t: &Token) -> &str {
+    match t.value {
+        TokenKind::Ident(ref ident) => &ident.name,
+        _ => "",
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match self {
+            Error::Read(_, e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+pub(super) type Result<T> = StdResult<T, crate::Error>;
+
+#[derive(Debug, Default, Clone)]
+pub struct Config {
+    // searched in order for <file>, and for "file" after the including file's directory.
+    pub include_paths: Vec<PathBuf>,
+    // applied in order after the predefined macros, like -D and -U.
+    pub defines: Vec<Define>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Define {
+    Macro(String, String), // name, with parameters if any, and replacement
+    Undef(String),
+}
+
+// expands directives and macros in stream, the tokens of the main file of sources.
+// files read on the way are added to sources. lexical errors in lines which are not skipped
+// go to diagnostics, up to its limit.
+pub fn preprocess(
+    sources: &mut SourceMap,
+    stream: Stream,
+    config: &Config,
+    diagnostics: &mut Diagnostics,
+) -> Result<Stream> {
+    let eof = stream.last().cloned();
+    let mut out: Stream = run(sources, stream, config, diagnostics)?
+        .into_iter()
+        .map(|token| token.token)
+        .collect();
+    out.extend(eof);
+    lex::concat_strings(out).map_err(crate::Error::from)
+}
+
+// preprocesses like preprocess, writing the result as source text as cpp -E does.
+pub fn preprocess_text(
+    sources: &mut SourceMap,
+    stream: Stream,
+    config: &Config,
+    diagnostics: &mut Diagnostics,
+) -> Result<String> {
+    let mut text = String::new();
+    let mut prev: Option<PpToken> = None;
+    for token in run(sources, stream, config, diagnostics)? {
+        if let Some(ref prev) = prev {
+            if token.bol {
+                text.push('\n');
+            } else if token.space || would_paste(prev, &token) {
+                text.push(' ');
+            }
+        }
+        text.push_str(&token.spelling);
+        prev = Some(token);
+    }
+    if prev.is_some() {
+        text.push('\n');
+    }
+    Ok(text)
+}
+
+fn run(
+    sources: &mut SourceMap,
+    stream: Stream,
+    config: &Config,
+    diagnostics: &mut Diagnostics,
+) -> Result<Vec<PpToken>> {
+    let mut out = Vec::new();
+    let path = PathBuf::from(&sources.main().name);
+    let mut pp = Preprocessor::new(config, sources, diagnostics);
+    pp.predefine()?;
+    pp.file(&path, stream, &mut out)?;
+    Ok(out)
+}
+
+// whether two tokens out of different places read as something else when written together.
+fn would_paste(lhs: &PpToken, rhs: &PpToken) -> bool {
+    if lhs.token.expansion.is_none() && rhs.token.expansion.is_none() {
+        return false;
+    }
+    let text = format!("{}{}", lhs.spelling, rhs.spelling);
+    lex::tokenize(&text).map_or(true, |stream| stream.len() != 3)
+}
+
+#[derive(Debug, Clone)]
+pub(super) struct PpToken {
+    pub(super) token: Token,
+    pub(super) spelling: String,         // as written, for '#' and '##'
+    pub(super) bol: bool,                // first token of a line
+    pub(super) space: bool,              // preceded by white space
+    pub(super) hideset: HashSet<String>, // macros this token came out of, they are not expanded again
+}
+
+impl PpToken {
+    pub(super) fn ident(&self) -> Option<&str> {
+        match self.token.value {
+            TokenKind::Ident(ref ident) => Some(&ident.name),
+            _ => None,
+        }
+    }
+    pub(super) fn is(&self, kind: TokenKind) -> bool {
+        self.token.is_kind(kind)
+    }
+}
+
+struct Preprocessor<'a> {
+    config: &'a Config,
+    sources: &'a mut SourceMap, // the files read, tokens are located in them
+    diagnostics: &'a mut Diagnostics,
+    macros: HashMap<String, Macro>,
+    depth: usize,           // include nesting
+    conds: Vec<Cond>,       // #if groups being read, innermost last
+    source: Source,         // the file being read
+    once: HashSet<PathBuf>, // files with #pragma once
+    counter: Cell<u64>,     // next __COUNTER__
+}
+
+// the file being read.
+struct Source {
+    base: usize, // conds opened in this file start here
+}
+
+impl Source {
+    fn new(base: usize) -> Self {
+        Self { base }
+    }
+}
+
+// an #if, #ifdef or #ifndef up to its #endif.
+struct Cond {
+    directive: Token,
+    included: bool, // the current group is compiled
+    taken: bool,    // some group was or will never be included, the rest are skipped
+    has_else: bool,
+}
+
+impl<'a> Preprocessor<'a> {
+    fn new(config: &'a Config, sources: &'a mut SourceMap, diagnostics: &'a mut Diagnostics) -> Self {
+        Self {
+            config,
+            sources,
+            diagnostics,
+            macros: HashMap::new(),
+            depth: 0,
+            conds: Vec::new(),
+            source: Source::new(0),
+            once: HashSet::new(),
+            counter: Cell::new(0),
+        }
+    }
+
+    // defines the macros every translation unit starts with.
+    fn predefine(&mut self) -> Result<()> {
+        let (date, time) = timestamp(now());
+        let mut text = String::new();
+        for (name, value) in PREDEFINED {
+            text.push_str(&format!("#define {} {}\n", name, value));
+        }
+        text.push_str(&format!("#define __DATE__ \"{}\"\n", date));
+        text.push_str(&format!("#define __TIME__ \"{}\"\n", time));
+        let stream = self.read(Path::new("<built-in>"), text);
+        self.file(Path::new("<built-in>"), stream, &mut Vec::new())?;
+
+        for &(name, builtin) in &[
+            ("__FILE__", Builtin::File),
+            ("__LINE__", Builtin::Line),
+            ("__COUNTER__", Builtin::Counter),
+        ] {
+            self.macros.insert(name.to_owned(), Macro::builtin(builtin));
+        }
+
+        let mut text = String::new();
+        for define in &self.config.defines {
+            match define {
+                Define::Macro(name, body) => text.push_str(&format!("#define {} {}\n", name, body)),
+                Define::Undef(name) => text.push_str(&format!("#undef {}\n", name)),
+            }
+        }
+        let stream = self.read(Path::new("<command-line>"), text);
+        self.file(Path::new("<command-line>"), stream, &mut Vec::new())
+    }
+
+    // adds text to the sources as the file at path, returns its tokens.
+    fn read(&mut self, path: &Path, text: String) -> Stream {
+        let file = self.sources.add(&path.display().to_string(), text);
+        lex::tokenize_deferred(&file.text, file.base)
+    }
+
+    // the name of the file and the line pos is in, as presumed after #line.
+    fn presumed(&self, pos: usize) -> (&str, i64) {
+        match self.sources.file(pos) {
+            Some(file) => file.presumed(file.line(pos)),
+            None => ("", 0),
+        }
+    }
+
+    fn file(&mut self, path: &Path, stream: Stream, out: &mut Vec<PpToken>) -> Result<()> {
+        let source = mem::replace(&mut self.source, Source::new(self.conds.len()));
+        let mut tokens = lines(self.sources, stream);
+        self.text_errors(&mut tokens);
+        while let Some(token) = tokens.pop_front() {
+            if token.bol && token.token.is_kind(TokenKind::Hash) {
+                let mut line = Vec::new();
+                while tokens.front().is_some_and(|t| !t.bol) {
+                    line.extend(tokens.pop_front());
+                }
+                let line = self.directive_errors(line);
+                self.directive(path, line, out)?;
+                self.text_errors(&mut tokens);
+                continue;
+            }
+            if self.skipping() {
+                continue;
+            }
+            if !self.expand(&token, &mut tokens)? {
+                out.push(token);
+            }
+        }
+        // conditionals do not span files.
+        if let Some(cond) = self.conds.get(self.source.base) {
+            return Err(Error::UnterminatedConditional(cond.directive.clone()).into());
+        }
+        self.source = source;
+        Ok(())
+    }
+
+    fn skipping(&self) -> bool {
+        self.conds.last().is_some_and(|cond| !cond.included)
+    }
+
+    // reports the lexical errors in the lines up to the next directive if they are not
+    // skipped, and takes them out. those in skipped lines are dropped with the lines.
+    fn text_errors(&mut self, tokens: &mut VecDeque<PpToken>) {
+        if self.skipping() {
+            return;
+        }
+        let mut i = 0;
+        while let Some(t) = tokens.get(i) {
+            if t.bol && t.is(TokenKind::Hash) {
+                return;
+            }
+            match t.token.error() {
+                Some(e) => {
+                    self.report(e);
+                    tokens.remove(i);
+                }
+                None => i += 1,
+            }
+        }
+    }
+
+    // takes the lexical errors out of a directive line, and reports them if the line is not
+    // skipped. the #elif of a group none was taken for is evaluated even so.
+    fn directive_errors(&mut self, line: Vec<PpToken>) -> Vec<PpToken> {
+        let elif = line.first().and_then(PpToken::ident) == Some("elif");
+        let live = !self.skipping() || elif && self.conds.last().is_some_and(|cond| !cond.taken);
+        let (errors, line): (Vec<PpToken>, Vec<PpToken>) =
+            line.into_iter().partition(|t| t.token.error().is_some());
+        if live {
+            errors.iter().filter_map(|t| t.token.error()).for_each(|e| self.report(e));
+        }
+        line
+    }
+
+    fn report(&mut self, e: lex::Error) {
+        if !self.diagnostics.limit_reached() {
+            self.diagnostics.report(e.diagnostic());
+        }
+    }
+
+    fn directive(
+        &mut self,
+        path: &Path,
+        line: Vec<PpToken>,
+        out: &mut Vec<PpToken>,
+    ) -> Result<()> {
+        // a lone '#' is the null directive.
+        let name = match line.first() {
+            Some(name) => name,
+            None => return Ok(()),
+        };
+        let (directive, args) = (&line[0], &line[1..]);
+        match name.ident() {
+            Some("if") => self.if_group(directive, |pp| pp.condition(directive, args)),
+            Some("ifdef") => self.if_group(directive, |pp| pp.is_defined(directive, args)),
+            Some("ifndef") => self.if_group(directive, |pp| Ok(!pp.is_defined(directive, args)?)),
+            Some("elif") => self.elif(directive, args),
+            Some("else") => self.else_group(directive),
+            Some("endif") => self.endif(directive),
+            // other lines in skipped groups need not be directives at all.
+            _ if self.skipping() => Ok(()),
+            Some("include") => self.include(path, directive, args, out),
+            Some("define") => self.define(directive, args),
+            Some("undef") => self.undef(directive, args),
+            Some("error") => {
+                let message = message(self.sources, args);
+                Err(Error::Reported(directive.token.clone(), message).into())
+            }
+            Some("warning") => {
+                let message = format!("#warning {}", message(self.sources, args));
+                self.diagnostics.report(Diagnostic::warning(message).at_token(&directive.token));
+                Ok(())
+            }
+            Some("pragma") => self.pragma(path, args),
+            Some("line") => self.line_directive(directive, args),
+            // "#" num "file"? is the line marker cpp outputs, the same as #line.
+            None if name.token.value.is_number() => self.line_directive(directive, &line),
+            _ => Err(Error::UnknownDirective(name.token.clone()).into()),
+        }
+    }
+
+    // starts a group which is included if cond holds. cond is not checked in a skipped group.
+    fn if_group(
+        &mut self,
+        directive: &PpToken,
+        cond: impl FnOnce(&Self) -> Result<bool>,
+    ) -> Result<()> {
+        let skipped = self.skipping();
+        let included = !skipped && cond(self)?;
+        self.conds.push(Cond {
+            directive: directive.token.clone(),
+            included,
+            taken: skipped || included,
+            has_else: false,
+        });
+        Ok(())
+    }
+
+    // elif = "elif" constant-expression
+    fn elif(&mut self, directive: &PpToken, args: &[PpToken]) -> Result<()> {
+        let i = self.next_group(directive)?;
+        let included = !self.conds[i].taken && self.condition(directive, args)?;
+        let cond = &mut self.conds[i];
+        cond.included = included;
+        cond.taken |= included;
+        Ok(())
+    }
+
+    fn else_group(&mut self, directive: &PpToken) -> Result<()> {
+        let i = self.next_group(directive)?;
+        let cond = &mut self.conds[i];
+        cond.included = !cond.taken;
+        cond.taken = true;
+        cond.has_else = true;
+        Ok(())
+    }
+
+    fn endif(&mut self, directive: &PpToken) -> Result<()> {
+        self.innermost(directive)?;
+        self.conds.pop();
+        Ok(())
+    }
+
+    // index of the conditional #elif, #else or #endif belongs to.
+    fn innermost(&self, directive: &PpToken) -> Result<usize> {
+        if self.conds.len() <= self.source.base {
+            return Err(Error::StrayConditional(directive.token.clone()).into());
+        }
+        Ok(self.conds.len() - 1)
+    }
+
+    // index of the conditional a new #elif or #else group belongs to.
+    fn next_group(&self, directive: &PpToken) -> Result<usize> {
+        let i = self.innermost(directive)?;
+        if self.conds[i].has_else {
+            return Err(Error::ElseAfterElse(directive.token.clone()).into());
+        }
+        Ok(i)
+    }
+
+    // ifdef = "ifdef" ident
+    fn is_defined(&self, directive: &PpToken, args: &[PpToken]) -> Result<bool> {
+        let name = args
+            .first()
+            .and_then(PpToken::ident)
+            .ok_or_else(|| Error::MacroNameRequired(directive.token.clone()))?;
+        Ok(self.macros.contains_key(name))
+    }
+
+    // evaluates the expression of #if or #elif. defined is resolved before macros are
+    // expanded, identifiers remaining after that are 0. the suffix of a number like 1u is
+    // part of it, before and after expansion.
+    fn condition(&self, directive: &PpToken, args: &[PpToken]) -> Result<bool> {
+        if args.is_empty() {
+            return Err(Error::InvalidExpression(directive.token.clone()).into());
+        }
+        let args = &expr::join_numbers(args.to_vec());
+        let mut tokens = Vec::new();
+        let mut i = 0;
+        while i < args.len() {
+            let t = &args[i];
+            if t.ident() != Some("defined") {
+                tokens.push(t.clone());
+                i += 1;
+                continue;
+            }
+            // defined = "defined" (ident | "(" ident ")")
+            let paren = args.get(i + 1).is_some_and(|t| t.is(TokenKind::LParen));
+            let name = if paren { args.get(i + 2) } else { args.get(i + 1) };
+            let defined = self.is_defined(t, name.map(slice::from_ref).unwrap_or_default())?;
+            if paren {
+                match args.get(i + 3) {
+                    Some(t) if t.is(TokenKind::RParen) => (),
+                    _ => return Err(Error::MacroNameRequired(t.token.clone()).into()),
+                }
+            }
+            tokens.push(number(t, defined.into()));
+            i += if paren { 4 } else { 2 };
+        }
+        let tokens: Vec<PpToken> = expr::join_numbers(self.expand_all(tokens)?)
+            .into_iter()
+            .map(|t| if t.token.is_ident() { number(&t, 0) } else { t })
+            .collect();
+        expr::eval(directive, &tokens)
+    }
+
+    // line = "line" num "file"?
+    // the line after the directive is num, in file if given. macros are expanded.
+    fn line_directive(&mut self, directive: &PpToken, args: &[PpToken]) -> Result<()> {
+        let args = self.expand_all(args.to_vec())?;
+        let invalid = || Error::InvalidLine(directive.token.clone());
+        let n = match args.first().map(|t| &t.token.value) {
+            Some(&TokenKind::Number(n)) if args.len() <= 2 => n,
+            _ => return Err(invalid().into()),
+        };
+        let name = match args.get(1).map(|t| &t.token.value) {
+            Some(TokenKind::Str(lit)) => Some(lit.value.iter().filter_map(|&c| char::from_u32(c)).collect()),
+            Some(_) => return Err(invalid().into()),
+            None => None,
+        };
+        self.sources.set_line(directive.token.loc.0, n as i64, name);
+        Ok(())
+    }
+
+    // pragma = "pragma" "once" | "pragma" token*
+    // unknown pragmas are ignored.
+    fn pragma(&mut self, path: &Path, args: &[PpToken]) -> Result<()> {
+        if args.first().and_then(PpToken::ident) == Some("once") {
+            self.once.insert(canonical(path));
+        }
+        Ok(())
+    }
+
+    // include = "include" ("\"" file "\"" | "<" file ">" | tokens expanding to "\"" file "\"")
+    fn include(
+        &mut self,
+        path: &Path,
+        directive: &PpToken,
+        args: &[PpToken],
+        out: &mut Vec<PpToken>,
+    ) -> Result<()> {
+        let invalid = || Error::InvalidInclude(directive.token.clone());
+        let (name, quoted) = match args.first().map(|t| &t.token.value) {
+            // the name is taken as written, escapes do not apply in header names.
+            Some(TokenKind::Str(_)) => {
+                let loc = &args[0].token.loc;
+                (self.sources.text(&Loc(loc.0 + 1, loc.1 - 1)).to_owned(), true)
+            }
+            Some(TokenKind::Lt) => {
+                let gt = args
+                    .iter()
+                    .find(|t| t.token.is_kind(TokenKind::Gt))
+                    .ok_or_else(invalid)?;
+                let name = self.sources.text(&Loc(args[0].token.loc.1, gt.token.loc.0));
+                (name.to_owned(), false)
+            }
+            Some(_) => match self.expand_all(args.to_vec())?.as_slice() {
+                [PpToken {
+                    token:
+                        Token {
+                            value: TokenKind::Str(lit),
+                            ..
+                        },
+                    ..
+                }] => (
+                    lit.value.iter().filter_map(|&c| char::from_u32(c)).collect(),
+                    true,
+                ),
+                _ => return Err(invalid().into()),
+            },
+            None => return Err(invalid().into()),
+        };
+
+        let dir = if quoted { path.parent() } else { None };
+        let file = dir
+            .into_iter()
+            .chain(self.config.include_paths.iter().map(PathBuf::as_path))
+            .map(|dir| dir.join(&name))
+            .find(|file| file.is_file())
+            .ok_or_else(|| Error::IncludeNotFound(directive.token.clone(), name))?;
+        if self.depth >= MAX_INCLUDE_DEPTH {
+            return Err(Error::IncludeTooDeep(directive.token.clone()).into());
+        }
+
+        if self.once.contains(&canonical(&file)) {
+            return Ok(());
+        }
+
+        let text = fs::read_to_string(&file).map_err(|e| Error::Read(file.clone(), e))?;
+        let stream = self.read(&file, text);
+        self.depth += 1;
+        let result = self.file(&file, stream, out);
+        self.depth -= 1;
+        result
+    }
+
+    // define = "define" ident ("(" params? ")")? token*
+    fn define(&mut self, directive: &PpToken, args: &[PpToken]) -> Result<()> {
+        let (name, m) = Macro::parse(directive, args)?;
+        self.macros.insert(name, m);
+        Ok(())
+    }
+
+    // undef = "undef" ident
+    fn undef(&mut self, directive: &PpToken, args: &[PpToken]) -> Result<()> {
+        let name = args
+            .first()
+            .and_then(PpToken::ident)
+            .ok_or_else(|| Error::MacroNameRequired(directive.token.clone()))?;
+        self.macros.remove(name);
+        Ok(())
+    }
+
+    // replaces token with its expansion at the front of tokens if it is a macro invocation.
+    fn expand(&self, token: &PpToken, tokens: &mut VecDeque<PpToken>) -> Result<bool> {
+        let name = match token.ident() {
+            Some(name) if !token.hideset.contains(name) => name,
+            _ => return Ok(false),
+        };
+        let m = match self.macros.get(name) {
+            Some(m) => m,
+            None => return Ok(false),
+        };
+
+        let mut hideset = token.hideset.clone();
+        let args = if m.is_function() {
+            // a function-like macro name without arguments is left as is.
+            if !tokens.front().is_some_and(|t| t.is(TokenKind::LParen)) {
+                return Ok(false);
+            }
+            let (args, rparen) = collect_args(token, tokens, m.arg_limit())?;
+            // the expansion may not hide more than the invocation, which ends at ')'.
+            hideset.retain(|name| rparen.hideset.contains(name));
+            m.arguments(token, args)?
+        } else {
+            Vec::new()
+        };
+        hideset.insert(name.to_owned());
+
+        let expansion = Rc::new(Expansion {
+            name: name.to_owned(),
+            loc: token.token.loc.clone(),
+            parent: token.token.expansion.clone(),
+        });
+        let mut expanded = match m.as_builtin() {
+            Some(builtin) => vec![self.builtin(builtin, token, &expansion)],
+            None => m.substitute(&args, &expansion, &mut |arg| self.expand_all(arg))?,
+        };
+        for (i, t) in expanded.iter_mut().enumerate() {
+            t.hideset.extend(hideset.iter().cloned());
+            t.bol = false;
+            if i == 0 {
+                t.space = token.space;
+            }
+        }
+        expanded.into_iter().rev().for_each(|t| tokens.push_front(t));
+        Ok(true)
+    }
+
+    // the token a dynamic macro stands for at the invocation token.
+    fn builtin(&self, builtin: Builtin, token: &PpToken, expansion: &Rc<Expansion>) -> PpToken {
+        let (value, spelling) = match builtin {
+            Builtin::File => {
+                let name = self.presumed(site(&token.token).0).0;
+                let lit = StrLit {
+                    encoding: Encoding::Char,
+                    value: name.bytes().map(u32::from).collect(),
+                };
+                (TokenKind::Str(lit), format!("{:?}", name))
+            }
+            Builtin::Line => {
+                // a macro spanning lines is on the line its outermost invocation starts.
+                let line = self.presumed(site(&token.token).0).1.max(0) as u64;
+                (TokenKind::Number(line), line.to_string())
+            }
+            Builtin::Counter => {
+                let n = self.counter.get();
+                self.counter.set(n + 1);
+                (TokenKind::Number(n), n.to_string())
+            }
+        };
+        PpToken {
+            token: Token {
+                value,
+                loc: token.token.loc.clone(),
+                expansion: Some(expansion.clone()),
+            },
+            spelling,
+            ..token.clone()
+        }
+    }
+
+    // expands every macro in tokens, used for directive and macro arguments.
+    fn expand_all(&self, tokens: Vec<PpToken>) -> Result<Vec<PpToken>> {
+        let mut tokens = VecDeque::from(tokens);
+        let mut out = Vec::new();
+        while let Some(token) = tokens.pop_front() {
+            if !self.expand(&token, &mut tokens)? {
+                out.push(token);
+            }
+        }
+        Ok(out)
+    }
+}
+
+// seconds since the epoch for __DATE__ and __TIME__, SOURCE_DATE_EPOCH makes builds reproducible.
+fn now() -> u64 {
+    env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |d| d.as_secs())
+        })
+}
+
+// __DATE__ and __TIME__ of secs since the epoch, in UTC.
+fn timestamp(secs: u64) -> (String, String) {
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let time = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+
+    // civil date of days since 1970-01-01, years start in march so leap days come last.
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z % 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = (mp + 2) % 12;
+    let year = yoe + era * 400 + u64::from(month < 2);
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let date = format!("{} {:>2} {}", MONTHS[month as usize], day, year);
+    (date, time)
+}
+
+// the rest of a directive line as written, for #error and #warning.
+fn message(sources: &SourceMap, args: &[PpToken]) -> String {
+    match (args.first(), args.last()) {
+        (Some(first), Some(last)) => sources.text(&Loc(first.token.loc.0, last.token.loc.1)).to_owned(),
+        _ => String::new(),
+    }
+}
+
+// a number replacing t in #if, like defined(X) or an identifier which is not a macro.
+fn number(t: &PpToken, n: u64) -> PpToken {
+    PpToken {
+        token: Token {
+            value: TokenKind::Number(n),
+            ..t.token.clone()
+        },
+        spelling: n.to_string(),
+        ..t.clone()
+    }
+}
+
+// the same file included by different paths is still the same for #pragma once.
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_owned())
+}
+
+// reads the arguments of a macro invocation up to the matching ')', which is returned too.
+// once limit arguments are read, the rest including commas goes to the last one.
+fn collect_args(
+    name: &PpToken,
+    tokens: &mut VecDeque<PpToken>,
+    limit: usize,
+) -> Result<(Vec<Vec<PpToken>>, PpToken)> {
+    tokens.pop_front(); // '('
+    let mut args = vec![Vec::new()];
+    let mut depth = 0;
+    while let Some(token) = tokens.pop_front() {
+        match token.token.value {
+            TokenKind::RParen if depth == 0 => return Ok((args, token)),
+            TokenKind::Comma if depth == 0 && args.len() < limit => {
+                args.push(Vec::new());
+                continue;
+            }
+            TokenKind::LParen => depth += 1,
+            TokenKind::RParen => depth -= 1,
+            _ => (),
+        }
+        args.last_mut().unwrap().push(token);
+    }
+    Err(Error::UnterminatedCall(name.token.clone()).into())
+}
+
+// attaches line information from sources to the tokens of stream, dropping the trailing eof.
+fn lines(sources: &SourceMap, stream: Stream) -> VecDeque<PpToken> {
+    let mut end = None;
+    stream
+        .into_iter()
+        .filter(|token| !token.is_kind(TokenKind::Eof))
+        .map(|token| {
+            let bol = end.is_none_or(|end| has_newline(sources.text(&Loc(end, token.loc.0))));
+            let space = end.is_some_and(|end| end < token.loc.0);
+            end = Some(token.loc.1);
+            PpToken {
+                spelling: sources.text(&token.loc).to_owned(),
+                token,
+                bol,
+                space,
+                hideset: HashSet::new(),
+            }
+        })
+        .collect()
+}
+
+// whether the text between two tokens ends a line. comments and spliced lines do not.
+fn has_newline(gap: &str) -> bool {
+    let bytes = gap.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'\n', _) => return true,
+            (b'\\', Some(b'\n')) => i += 2,
+            (b'/', Some(b'/')) => i = gap[i..].find('\n').map_or(bytes.len(), |n| i + n),
+            (b'/', Some(b'*')) => {
+                i = gap[i + 2..].find("*/").map_or(bytes.len(), |n| i + 2 + n + 2)
+            }
+            _ => i += 1,
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+#[path = "./preprocessor_test.rs"]
+mod preprocessor_test;
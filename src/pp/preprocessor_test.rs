@@ -0,0 +1,543 @@
+use super::*;
+use crate::{
+    diagnostic::Severity,
+    lex::{token::Loc, tokenize, tokenize_deferred, Encoding, Ident, StrLit},
+};
+use std::{env, process};
+
+#[test]
+fn define_test() {
+    assert_eq!(
+        pp("#define N 3\n#define M N + N\nM * N"),
+        vec![num(3), plus(), num(3), asterisk(), num(3)],
+    );
+    assert_eq!(pp("#define N 3\n#undef N\nN"), vec![ident("N")]);
+    // a directive must start a line.
+    assert_eq!(
+        pp("1 # define"),
+        vec![num(1), TokenKind::Hash, ident("define")]
+    );
+    // splices and comments do not end the directive line.
+    assert_eq!(pp("#define N 1 \\\n + 2 /*\n*/ + 3\nN"), vec![num(1), plus(), num(2), plus(), num(3)]);
+    assert_eq!(pp("#\n1"), vec![num(1)]);
+}
+
+#[test]
+fn recursive_define_test() {
+    assert_eq!(pp("#define a a + 1\na"), vec![ident("a"), plus(), num(1)]);
+    assert_eq!(
+        pp("#define a b\n#define b a\na b"),
+        vec![ident("a"), ident("b")],
+    );
+}
+
+#[test]
+fn include_test() {
+    let dir = temp_dir("include");
+    fs::create_dir_all(dir.join("sys")).unwrap();
+    fs::write(dir.join("one.h"), "#define ONE 1\n").unwrap();
+    fs::write(dir.join("sys/two.h"), "#include \"../one.h\"\nONE + 1\n").unwrap();
+
+    let main = dir.join("main.c");
+    let input = "#include \"one.h\"\nONE;";
+    let stream = preprocess(&mut sources(&main, input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()).unwrap();
+    assert_eq!(kinds(stream), vec![num(1), TokenKind::SemiColon, TokenKind::Eof]);
+
+    let config = Config {
+        include_paths: vec![dir.clone()],
+        ..Config::default()
+    };
+    let input = "#include <sys/two.h>\n#define TWO \"sys/two.h\"\n#include TWO\n";
+    let stream = preprocess(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &config, &mut Diagnostics::default()).unwrap();
+    assert_eq!(
+        kinds(stream),
+        vec![num(1), plus(), num(1), num(1), plus(), num(1), TokenKind::Eof],
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn include_error_test() {
+    let dir = temp_dir("include_error");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("self.h"), "#include \"self.h\"\n").unwrap();
+
+    let main = dir.join("main.c");
+    let input = "#include \"self.h\"";
+    assert!(matches!(
+        preprocess(&mut sources(&main, input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()),
+        Err(crate::Error::Preprocessor(Error::IncludeTooDeep(_)))
+    ));
+
+    let input = "#include \"none.h\"";
+    assert!(matches!(
+        preprocess(&mut sources(&main, input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()),
+        Err(crate::Error::Preprocessor(Error::IncludeNotFound(ref t, ref name)))
+            if t.loc == Loc(1, 8) && name == "none.h"
+    ));
+
+    let input = "#include 1";
+    assert!(matches!(
+        preprocess(&mut sources(&main, input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()),
+        Err(crate::Error::Preprocessor(Error::InvalidInclude(_)))
+    ));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn directive_error_test() {
+    let input = "#foo";
+    assert!(matches!(
+        preprocess(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()),
+        Err(crate::Error::Preprocessor(Error::UnknownDirective(ref t))) if t.loc == Loc(1, 4)
+    ));
+    let input = "#define 1";
+    assert!(matches!(
+        preprocess(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()),
+        Err(crate::Error::Preprocessor(Error::MacroNameRequired(_)))
+    ));
+}
+
+#[test]
+fn function_like_define_test() {
+    assert_eq!(
+        pp("#define ADD(a, b) a + b\nADD(1, 2 * 3)"),
+        vec![num(1), plus(), num(2), asterisk(), num(3)],
+    );
+    // arguments are expanded before substitution, parentheses protect commas.
+    assert_eq!(
+        pp("#define N 2\n#define F(x) x\nF((N, 1))"),
+        vec![
+            TokenKind::LParen,
+            num(2),
+            TokenKind::Comma,
+            num(1),
+            TokenKind::RParen
+        ],
+    );
+    assert_eq!(pp("#define F() 1\nF()"), vec![num(1)]);
+    // without '(' the name is not an invocation, nor is a space before the parameters.
+    assert_eq!(pp("#define F(x) x\nF"), vec![ident("F")]);
+    assert_eq!(
+        pp("#define F (x) x\nF"),
+        vec![TokenKind::LParen, ident("x"), TokenKind::RParen, ident("x")],
+    );
+    // the invocation may span lines.
+    assert_eq!(pp("#define F(x) x\nF(\n1\n)"), vec![num(1)]);
+}
+
+#[test]
+fn stringize_and_paste_test() {
+    assert_eq!(
+        pp("#define S(x) #x\nS( a  +\n\"b\\n\" )"),
+        vec![str("a + \"b\\n\"")],
+    );
+    assert_eq!(pp("#define S(x) #x\nS()"), vec![str("")]);
+    assert_eq!(pp("#define P(a, b) a ## b\nP(1, 2)"), vec![num(12)]);
+    assert_eq!(pp("#define P(a, b) a ## b\nP(x, y)"), vec![ident("xy")]);
+    // an empty argument pastes as nothing.
+    assert_eq!(pp("#define P(a, b) a ## b\nP(, 2) P(1, )"), vec![num(2), num(1)]);
+    // operands of '##' are not expanded, the result is rescanned.
+    assert_eq!(
+        pp("#define N 1\n#define NN 2\n#define P(a) a ## N\nP(N)"),
+        vec![num(2)],
+    );
+    assert_eq!(
+        pp("#define P(a, b) a ## b\nP(=, =) P(<, =)"),
+        vec![TokenKind::Eq, TokenKind::Le],
+    );
+}
+
+#[test]
+fn variadic_define_test() {
+    assert_eq!(
+        pp("#define F(...) __VA_ARGS__\nF(1, 2)"),
+        vec![num(1), TokenKind::Comma, num(2)],
+    );
+    assert_eq!(
+        pp("#define F(a, ...) a __VA_OPT__(, __VA_ARGS__)\nF(1) F(1, 2)"),
+        vec![num(1), num(1), TokenKind::Comma, num(2)],
+    );
+    assert_eq!(pp("#define F(a, ...) #__VA_ARGS__\nF(1, 2,3)"), vec![str("2,3")]);
+}
+
+#[test]
+fn blue_paint_test() {
+    assert_eq!(
+        pp("#define f(x) x f\nf(1)(2)"),
+        vec![num(1), ident("f"), TokenKind::LParen, num(2), TokenKind::RParen],
+    );
+    assert_eq!(
+        pp("#define f(x) g(x\n#define g(x) x + f\nf(1))"),
+        vec![num(1), plus(), ident("f")],
+    );
+    // a painted name stays unexpanded when it is passed on as an argument.
+    assert_eq!(
+        pp("#define foo foo\n#define F(x) x\nF(foo)"),
+        vec![ident("foo")],
+    );
+}
+
+#[test]
+fn expansion_history_test() {
+    let input = "#define ONE 1\n#define F(x) x + ONE\nF(2)";
+    let stream = preprocess(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()).unwrap();
+
+    // the argument was written at the invocation, '+' in the body of F.
+    assert_eq!(stream[0].expansion, None);
+    let plus = stream[1].expansion.as_ref().unwrap();
+    assert_eq!((plus.name.as_str(), plus.loc.clone()), ("F", Loc(35, 36)));
+    assert!(plus.parent.is_none());
+
+    let one = stream[2].expansion.as_ref().unwrap();
+    assert_eq!((one.name.as_str(), one.loc.clone()), ("ONE", Loc(31, 34)));
+    assert_eq!(one.parent.as_ref().unwrap().name, "F");
+    assert_eq!(stream[2].loc, Loc(12, 13));
+}
+
+#[test]
+fn macro_error_test() {
+    let error = |input: &str| {
+        preprocess(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()).unwrap_err()
+    };
+    assert!(matches!(
+        error("#define F(x) x\nF(1, 2)"),
+        crate::Error::Preprocessor(Error::ArgCount(_, 2, 1))
+    ));
+    assert!(matches!(
+        error("#define F(x, y) x\nF(1)"),
+        crate::Error::Preprocessor(Error::ArgCount(_, 1, 2))
+    ));
+    assert!(matches!(
+        error("#define F(x) x\nF(1"),
+        crate::Error::Preprocessor(Error::UnterminatedCall(_))
+    ));
+    assert!(matches!(
+        error("#define F(x, x) x"),
+        crate::Error::Preprocessor(Error::InvalidParams(_))
+    ));
+    assert!(matches!(
+        error("#define F(x) #y"),
+        crate::Error::Preprocessor(Error::StrayHash(_))
+    ));
+    assert!(matches!(
+        error("#define F(x) ## x"),
+        crate::Error::Preprocessor(Error::PasteAtEdge(_))
+    ));
+    assert!(matches!(
+        error("#define F(x) __VA_OPT__(x)"),
+        crate::Error::Preprocessor(Error::InvalidVaOpt(_))
+    ));
+    assert!(matches!(
+        error("#define P(a, b) a ## b\nP(+, -)"),
+        crate::Error::Preprocessor(Error::InvalidPaste(_, ref text)) if text == "+-"
+    ));
+}
+
+#[test]
+fn conditional_test() {
+    assert_eq!(pp("#if 1\n1\n#else\n2\n#endif"), vec![num(1)]);
+    assert_eq!(pp("#if 0\n1\n#elif 1\n2\n#elif 1\n3\n#else\n4\n#endif"), vec![num(2)]);
+    assert_eq!(pp("#if 0\n1\n#elif 0\n2\n#else\n3\n#endif"), vec![num(3)]);
+    assert_eq!(
+        pp("#define A\n#ifdef A\n1\n#endif\n#ifndef A\n2\n#endif\n#ifdef B\n3\n#endif"),
+        vec![num(1)],
+    );
+    // skipped groups may nest, and hold anything but a conditional.
+    assert_eq!(
+        pp("#if 0\n#if 1\n1\n#else\n2\n#endif\n#foo\n#else\n3\n#endif"),
+        vec![num(3)],
+    );
+    // directives in skipped groups are not run.
+    assert_eq!(pp("#if 0\n#define N 1\n#error no\n#endif\nN"), vec![ident("N")]);
+}
+
+#[test]
+fn skipped_lexical_error_test() {
+    let pp = |input: &str| {
+        let mut diagnostics = Diagnostics::default();
+        let stream = tokenize_deferred(input, 0);
+        let stream = preprocess(&mut sources(Path::new("main.c"), input), stream, &Config::default(), &mut diagnostics)
+            .unwrap();
+        let errors: Vec<String> = diagnostics.iter().map(|d| d.message.clone()).collect();
+        (kinds(stream), errors)
+    };
+    // lines in skipped groups need not be valid tokens.
+    let (kinds, errors) = pp("#if 0\ndon't\n#else\n1\n#endif\n");
+    assert_eq!(kinds, vec![num(1), TokenKind::Eof]);
+    assert!(errors.is_empty());
+
+    let (kinds, errors) = pp("#ifdef A\n$\n#elif 1 @\n1 ` 2\n#else\n'\n#endif\n");
+    assert_eq!(kinds, vec![num(1), num(2), TokenKind::Eof]);
+    assert_eq!(errors, vec!["invalid char '@'", "invalid char '`'"]);
+}
+
+#[test]
+fn non_ascii_char_test() {
+    let errors = |input: &str| {
+        let mut diagnostics = Diagnostics::default();
+        let stream = tokenize_deferred(input, 0);
+        preprocess(&mut sources(Path::new("main.c"), input), stream, &Config::default(), &mut diagnostics)
+            .unwrap();
+        let errors: Vec<(String, Option<Loc>)> =
+            diagnostics.iter().map(|d| (d.message.clone(), d.loc.clone())).collect();
+        errors
+    };
+    // the error covers every byte of the char.
+    assert_eq!(errors("a = 1;\né;"), vec![("invalid char 'é'".to_owned(), Some(Loc(7, 9)))]);
+    assert_eq!(errors("#define X é\n1"), vec![("invalid char 'é'".to_owned(), Some(Loc(10, 12)))]);
+}
+
+#[test]
+fn defined_test() {
+    let defines = "#define A 0\n#define F(x) x\n";
+    let cond = |cond: &str| pp(&format!("{}#if {}\n1\n#else\n0\n#endif", defines, cond));
+    assert_eq!(cond("defined A"), vec![num(1)]);
+    assert_eq!(cond("defined(F) && !defined ( B )"), vec![num(1)]);
+    // defined is resolved before A expands to 0.
+    assert_eq!(cond("defined A && !A"), vec![num(1)]);
+    // identifiers which are not macros are 0, macros are expanded.
+    assert_eq!(cond("B"), vec![num(0)]);
+    assert_eq!(cond("F(2) == 2"), vec![num(1)]);
+}
+
+#[test]
+fn constant_expression_test() {
+    let cond = |cond: &str| pp(&format!("#if {}\n1\n#else\n0\n#endif", cond));
+    assert_eq!(cond("1 + 2 * 3 == 7 && (1 + 2) * 3 == 9"), vec![num(1)]);
+    assert_eq!(cond("7 / 2 == 3 && 7 % 2 == 1 && -7 / 2 == -3"), vec![num(1)]);
+    assert_eq!(cond("(1 << 4 | 1) == 17 && (6 & 3 ^ 1) == 3 && ~0 == -1"), vec![num(1)]);
+    assert_eq!(cond("-1 >> 1 == -1 && 2 >= 2 && 1 < 2 && !(2 <= 1) && 3 > 2"), vec![num(1)]);
+    assert_eq!(cond("0 ? 1 : 0 || 0"), vec![num(0)]);
+    assert_eq!(cond("'a' == 97"), vec![num(1)]);
+    // a constant too large for intmax_t is unsigned, so is the arithmetic with it.
+    assert_eq!(cond("-1 < 0"), vec![num(1)]);
+    assert_eq!(cond("-1 < 18446744073709551615"), vec![num(0)]);
+    // octal, hex and suffixed constants, u makes one unsigned.
+    assert_eq!(cond("010 == 8 && 0 == 00 && 0x10 == 16 && 0XfF == 255"), vec![num(1)]);
+    assert_eq!(cond("10UL > 5 && 1ll == 1 && 0x10lu == 16"), vec![num(1)]);
+    assert_eq!(cond("-1 < 0u"), vec![num(0)]);
+    assert_eq!(cond("-1 < 0l"), vec![num(1)]);
+    assert_eq!(pp("#define N 0x10u
+#if N == 16 && -1 > N
+1
+#endif"), vec![num(1)]);
+    // operands which are not evaluated may divide by zero.
+    assert_eq!(cond("0 && 1 / 0 || 1 ? 1 : 1 % 0"), vec![num(1)]);
+}
+
+#[test]
+fn conditional_error_test() {
+    let error = |input: &str| {
+        preprocess(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()).unwrap_err()
+    };
+    assert!(matches!(
+        error("#if 1\n1"),
+        crate::Error::Preprocessor(Error::UnterminatedConditional(ref t)) if t.loc == Loc(1, 3)
+    ));
+    assert!(matches!(
+        error("#endif"),
+        crate::Error::Preprocessor(Error::StrayConditional(_))
+    ));
+    assert!(matches!(
+        error("#if 1\n#else\n#elif 1\n#endif"),
+        crate::Error::Preprocessor(Error::ElseAfterElse(_))
+    ));
+    assert!(matches!(
+        error("#if 1 +\n#endif"),
+        crate::Error::Preprocessor(Error::InvalidExpression(ref t)) if t.loc == Loc(1, 3)
+    ));
+    assert!(matches!(
+        error("#if (1\n#endif"),
+        crate::Error::Preprocessor(Error::InvalidExpression(_))
+    ));
+    assert!(matches!(
+        error("#if 1 2\n#endif"),
+        crate::Error::Preprocessor(Error::InvalidExpression(ref t)) if t.loc == Loc(6, 7)
+    ));
+    assert!(matches!(
+        error("#if 1 / 0\n#endif"),
+        crate::Error::Preprocessor(Error::DivisionByZero(ref t)) if t.loc == Loc(6, 7)
+    ));
+    assert!(matches!(
+        error("#if 09 || 1x || 0x\n#endif"),
+        crate::Error::Preprocessor(Error::InvalidNumber(ref t, ref n)) if t.loc == Loc(4, 6) && n == "09"
+    ));
+    assert!(matches!(
+        error("#if 1 || 10uu\n#endif"),
+        crate::Error::Preprocessor(Error::InvalidNumber(_, ref n)) if n == "10uu"
+    ));
+    assert!(matches!(
+        error("#if defined(\n#endif"),
+        crate::Error::Preprocessor(Error::MacroNameRequired(_))
+    ));
+    assert!(matches!(
+        error("#error  stop  here \n"),
+        crate::Error::Preprocessor(Error::Reported(_, ref message)) if message == "stop  here"
+    ));
+}
+
+#[test]
+fn warning_directive_test() {
+    let input = "1\n#warning  check  this \n#if 0\n#warning no\n#endif\n2";
+    let mut diagnostics = Diagnostics::default();
+    let stream = preprocess(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &Config::default(), &mut diagnostics).unwrap();
+    assert_eq!(kinds(stream), vec![num(1), num(2), TokenKind::Eof]);
+    let warnings: Vec<_> = diagnostics.iter().map(|d| (d.severity, d.message.as_str(), d.loc.clone())).collect();
+    assert_eq!(warnings, vec![(Severity::Warning, "#warning check  this", Some(Loc(3, 10)))]);
+}
+
+#[test]
+fn pragma_once_test() {
+    let dir = temp_dir("pragma_once");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("once.h"), "#pragma once\n1\n").unwrap();
+    fs::write(dir.join("twice.h"), "2\n").unwrap();
+
+    let main = dir.join("main.c");
+    let input = "#include \"once.h\"\n#include \"./once.h\"\n#include \"twice.h\"\n#include \"twice.h\"\n#pragma pack\n";
+    let stream = preprocess(&mut sources(&main, input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()).unwrap();
+    assert_eq!(kinds(stream), vec![num(1), num(2), num(2), TokenKind::Eof]);
+
+    fs::write(dir.join("open.h"), "#if 1\n").unwrap();
+    let input = "#include \"open.h\"\n#endif\n";
+    assert!(matches!(
+        preprocess(&mut sources(&main, input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()),
+        Err(crate::Error::Preprocessor(Error::UnterminatedConditional(_)))
+    ));
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn predefined_macro_test() {
+    assert_eq!(
+        pp("__STDC__ __STDC_VERSION__ __x86_64__ __linux__"),
+        vec![num(1), num(201112), num(1), num(1)],
+    );
+    assert_eq!(pp("__FILE__"), vec![str("main.c")]);
+    assert_eq!(pp("__COUNTER__ __COUNTER__ __COUNTER__"), vec![num(0), num(1), num(2)]);
+    // a macro is on the line of its outermost invocation.
+    assert_eq!(
+        pp("__LINE__\n#define L __LINE__\n#define F(x) x L\n\nF(\n__LINE__)"),
+        vec![num(1), num(6), num(5)],
+    );
+    assert_eq!(pp("#ifdef __LINE__\n1\n#endif"), vec![num(1)]);
+
+    let date = |stream: Vec<TokenKind>| match &stream[..] {
+        [TokenKind::Str(lit)] => lit.value.iter().map(|&c| c as u8 as char).collect::<String>(),
+        _ => panic!("{:?}", stream),
+    };
+    // like "Oct 18 2026" and "12:34:56"
+    let d = date(pp("__DATE__"));
+    assert_eq!((d.len(), &d[3..4], &d[6..7]), (11, " ", " "));
+    let t = date(pp("__TIME__"));
+    assert_eq!((t.len(), &t[2..3], &t[5..6]), (8, ":", ":"));
+}
+
+#[test]
+fn timestamp_test() {
+    assert_eq!(timestamp(0), ("Jan  1 1970".to_owned(), "00:00:00".to_owned()));
+    assert_eq!(timestamp(951_782_400 + 3661), ("Feb 29 2000".to_owned(), "01:01:01".to_owned()));
+    assert_eq!(timestamp(1_792_367_999), ("Oct 18 2026".to_owned(), "23:59:59".to_owned()));
+}
+
+#[test]
+fn line_directive_test() {
+    assert_eq!(
+        pp("#line 10\n__LINE__\n__LINE__ __FILE__\n#line 20 \"gen.c\"\n__LINE__ __FILE__"),
+        vec![num(10), num(11), str("main.c"), num(20), str("gen.c")],
+    );
+    // a line marker from preprocessed output and a macro expanding to the number.
+    assert_eq!(
+        pp("# 5 \"a.y\"\n__LINE__ __FILE__\n#define N 7\n#line N\n__LINE__"),
+        vec![num(5), str("a.y"), num(7)],
+    );
+
+    let input = "#line x";
+    assert!(matches!(
+        preprocess(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()),
+        Err(crate::Error::Preprocessor(Error::InvalidLine(_)))
+    ));
+}
+
+#[test]
+fn preprocess_text_test() {
+    let text = |input: &str| {
+        preprocess_text(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &Config::default(), &mut Diagnostics::default()).unwrap()
+    };
+    assert_eq!(
+        text("#define ADD(a, b) a+b\n#if 1\nx =  ADD(1,2);\n#endif\n  y;"),
+        "x = 1+2;\ny;\n",
+    );
+    // tokens which are not adjacent in the source are kept apart.
+    assert_eq!(text("#define LT <\n1 <LT 2"), "1 < < 2\n");
+    assert_eq!(text("#define S(x) #x\nS(a \"b\")"), "\"a \\\"b\\\"\"\n");
+    assert_eq!(text(""), "");
+}
+
+#[test]
+fn command_line_define_test() {
+    let config = Config {
+        defines: vec![
+            Define::Macro("N".to_owned(), "1".to_owned()),
+            Define::Macro("F(x)".to_owned(), "x + N".to_owned()),
+            Define::Macro("M".to_owned(), "1".to_owned()),
+            Define::Undef("M".to_owned()),
+            Define::Undef("__STDC__".to_owned()),
+        ],
+        ..Config::default()
+    };
+    let input = "F(2) M __STDC__";
+    let stream = preprocess(&mut sources(Path::new("main.c"), input), tokenize(input).unwrap(), &config, &mut Diagnostics::default()).unwrap();
+    assert_eq!(
+        kinds(stream),
+        vec![num(2), plus(), num(1), ident("M"), ident("__STDC__"), TokenKind::Eof],
+    );
+}
+
+fn pp(input: &str) -> Vec<TokenKind> {
+    let stream = tokenize(input).unwrap();
+    let mut kinds = kinds(preprocess(&mut sources(Path::new("main.c"), input), stream, &Config::default(), &mut Diagnostics::default()).unwrap());
+    assert_eq!(kinds.pop(), Some(TokenKind::Eof));
+    kinds
+}
+
+fn sources(path: &Path, input: &str) -> SourceMap {
+    SourceMap::new(&path.display().to_string(), input)
+}
+
+fn kinds(stream: Stream) -> Vec<TokenKind> {
+    stream.into_iter().map(|token| token.value).collect()
+}
+
+fn temp_dir(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("r9cc-{}-{}", process::id(), name))
+}
+
+fn num(n: u64) -> TokenKind {
+    TokenKind::Number(n)
+}
+
+fn plus() -> TokenKind {
+    TokenKind::Plus
+}
+
+fn asterisk() -> TokenKind {
+    TokenKind::Asterisk
+}
+
+fn str(s: &str) -> TokenKind {
+    TokenKind::Str(StrLit {
+        encoding: Encoding::Char,
+        value: s.bytes().map(u32::from).collect(),
+    })
+}
+
+fn ident(name: &str) -> TokenKind {
+    TokenKind::Ident(Ident {
+        name: name.to_owned(),
+    })
+}
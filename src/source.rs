@@ -0,0 +1,137 @@
+use crate::lex::token::Loc;
+use std::iter;
+
+// the files of a translation unit in one offset space, so a Loc alone tells the file.
+// the main file starts at 0, each file read after it starts past the end of the last.
+#[derive(Debug, Clone)]
+pub struct SourceMap {
+    files: Vec<File>,
+}
+
+#[derive(Debug, Clone)]
+pub struct File {
+    pub name: String,
+    pub text: String,
+    pub base: usize,         // offset of the first byte
+    line_starts: Vec<usize>, // offsets of the first byte of each line
+    presumed: Vec<Presumed>, // set by #line, in order of the lines they start at
+}
+
+// lines from line on are numbered shift more than they are, in the file named name.
+#[derive(Debug, Clone)]
+struct Presumed {
+    line: usize,
+    shift: i64,
+    name: String,
+}
+
+// a place in a file, lines and columns count from 1 and columns are in bytes like gcc.
+#[derive(Debug)]
+pub struct Position<'a> {
+    pub file: &'a File,
+    pub line: usize,
+    pub col: usize,
+}
+
+impl SourceMap {
+    pub fn new(name: &str, text: &str) -> Self {
+        Self {
+            files: vec![File::new(name.to_owned(), text.to_owned(), 0)],
+        }
+    }
+
+    // adds a file read for the unit, like an #include. its tokens are lexed at its base.
+    pub fn add(&mut self, name: &str, text: String) -> &File {
+        let last = self.files.last().unwrap();
+        // the end of a file is a position too, for eof.
+        let base = last.base + last.text.len() + 1;
+        self.files.push(File::new(name.to_owned(), text, base));
+        self.files.last().unwrap()
+    }
+
+    pub fn main(&self) -> &File {
+        &self.files[0]
+    }
+
+    // the file pos is in, up to and including its end.
+    pub fn file(&self, pos: usize) -> Option<&File> {
+        let i = self.files.partition_point(|file| file.base <= pos);
+        self.files[..i]
+            .last()
+            .filter(|file| pos <= file.base + file.text.len())
+    }
+
+    // the text at loc, which is in a single file.
+    pub fn text(&self, loc: &Loc) -> &str {
+        let file = self.file(loc.0).expect("loc out of the source map");
+        &file.text[loc.0 - file.base..loc.1 - file.base]
+    }
+
+    // numbers the line after the one pos is on as line, and those after it on from there,
+    // in the file named name if given, like #line.
+    pub fn set_line(&mut self, pos: usize, line: i64, name: Option<String>) {
+        let i = self.files.partition_point(|file| file.base <= pos);
+        let file = match self.files[..i].last_mut() {
+            Some(file) => file,
+            None => return,
+        };
+        let next = file.line(pos) + 1;
+        let name = name.unwrap_or_else(|| file.presumed(next).0.to_owned());
+        file.presumed.push(Presumed {
+            line: next,
+            shift: line - next as i64,
+            name,
+        });
+    }
+
+    pub fn position(&self, pos: usize) -> Option<Position<'_>> {
+        let file = self.file(pos)?;
+        if !file.text.is_char_boundary(pos - file.base) {
+            return None;
+        }
+        let line = file.line(pos);
+        let col = pos - file.line_starts[line - 1] + 1;
+        Some(Position { file, line, col })
+    }
+}
+
+impl File {
+    fn new(name: String, text: String, base: usize) -> Self {
+        let newlines = text.match_indices('\n').map(|(i, _)| base + i + 1);
+        Self {
+            line_starts: iter::once(base).chain(newlines).collect(),
+            presumed: Vec::new(),
+            name,
+            text,
+            base,
+        }
+    }
+
+    // the line pos is on.
+    pub fn line(&self, pos: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= pos)
+    }
+
+    // the name of the file and the number of line n as presumed after #line.
+    pub fn presumed(&self, n: usize) -> (&str, i64) {
+        let i = self.presumed.partition_point(|presumed| presumed.line <= n);
+        match self.presumed[..i].last() {
+            Some(presumed) => (&presumed.name, n as i64 + presumed.shift),
+            None => (&self.name, n as i64),
+        }
+    }
+
+    // the text of line n without its newline.
+    pub fn line_text(&self, n: usize) -> &str {
+        let start = self.line_starts[n - 1] - self.base;
+        let end = self
+            .line_starts
+            .get(n)
+            .map_or(self.text.len(), |&next| next - self.base - 1);
+        &self.text[start..end]
+    }
+}
+
+#[cfg(test)]
+#[path = "./source_test.rs"]
+mod source_test;
@@ -0,0 +1,44 @@
+use super::*;
+
+#[test]
+fn position_test() {
+    let mut sources = SourceMap::new("a.c", "1;\nab = 2;\n");
+    let base = sources.add("a.h", "x\ny".to_owned()).base;
+    assert_eq!(base, 12);
+
+    let pos = sources.position(6).unwrap();
+    assert_eq!((pos.file.name.as_str(), pos.line, pos.col), ("a.c", 2, 4));
+    assert_eq!(pos.file.line_text(pos.line), "ab = 2;");
+
+    // the end of a file is in it.
+    let pos = sources.position(11).unwrap();
+    assert_eq!((pos.file.name.as_str(), pos.line, pos.col), ("a.c", 3, 1));
+    assert_eq!(pos.file.line_text(pos.line), "");
+
+    let pos = sources.position(base + 2).unwrap();
+    assert_eq!((pos.file.name.as_str(), pos.line, pos.col), ("a.h", 2, 1));
+    assert_eq!(pos.file.line_text(pos.line), "y");
+
+    assert_eq!(sources.text(&Loc(3, 5)), "ab");
+    assert_eq!(sources.text(&Loc(base, base + 1)), "x");
+    assert!(sources.position(base + 4).is_none());
+}
+
+#[test]
+fn char_boundary_test() {
+    let sources = SourceMap::new("a.c", "\"é\" @");
+    assert_eq!(sources.position(5).unwrap().col, 6);
+    assert!(sources.position(2).is_none());
+}
+
+#[test]
+fn presumed_test() {
+    let mut sources = SourceMap::new("a.c", "1;\n#line 10\n2;\n3;\n#line 20 \"b.y\"\n4;\n");
+    sources.set_line(3, 10, None);
+    sources.set_line(18, 20, Some("b.y".to_owned()));
+    let file = sources.main();
+    assert_eq!(file.presumed(1), ("a.c", 1));
+    assert_eq!(file.presumed(3), ("a.c", 10));
+    assert_eq!(file.presumed(4), ("a.c", 11));
+    assert_eq!(file.presumed(6), ("b.y", 20));
+}
@@ -9,8 +9,8 @@ try() {
   expected="$1"
   input="$2"
 
-  ${CMD} "$input" > "${TARGET}/tmp.s"
-  gcc -o "${TARGET}/tmp" "${TARGET}/tmp.s"
+  printf '%s' "$input" > "${TARGET}/tmp.c"
+  ${CMD} -o "${TARGET}/tmp" "${TARGET}/tmp.c" || exit 1
   "${TARGET}/tmp"
   actual="$?"
 
@@ -22,36 +22,183 @@ try() {
   fi
 }
 
-try 0 0
-try 100 100
-try 2 '1+1'
-try 21 '3*(9-2)'
-try 14 '(3+3)+2*(5-1)'
-try 2 '-3+5'
-try 2 '4*-2+10'
-try 1 '-2+3'
-try 2 ' 1 +  1 '
-
-try 1 '0==0'
-try 1 '23==23'
-try 0 '0==1'
-try 1 '10!=20'
-try 0 '10!=10'
-
-try 1 '3>=2'
-try 1 '3>=3'
-try 0 '2>=3'
-try 1 '3>2'
-try 0 '2>3'
-
-try 1 '2<=3'
-try 1 '2<=2'
-try 0 '3<=2'
-try 1 '2<3'
-try 0 '3<2'
+try 0 '0;'
+try 100 '100;'
+try 2 '1+1;'
+try 21 '3*(9-2);'
+try 14 '(3+3)+2*(5-1);'
+try 2 '-3+5;'
+try 2 '4*-2+10;'
+try 1 '-2+3;'
+try 2 ' 1 +  1 ;'
+
+try 1 '0==0;'
+try 1 '23==23;'
+try 0 '0==1;'
+try 1 '10!=20;'
+try 0 '10!=10;'
+
+try 1 '3>=2;'
+try 1 '3>=3;'
+try 0 '2>=3;'
+try 1 '3>2;'
+try 0 '2>3;'
+
+try 1 '2<=3;'
+try 1 '2<=2;'
+try 0 '3<=2;'
+try 1 '2<3;'
+try 0 '3<2;'
 
 try 1 'a=1;'
 try 1 'a=b=1;'
 try 10 'a=10;a;'
 
+try 2 '1?2:3;'
+try 3 '0?2:3;'
+try 4 '0?2:1?4:5;'
+try 5 'a=0?1:5;a;'
+try 3 '1,2,3;'
+try 7 'a=1,b=6;a+b;'
+try 2 '(a=2,a==2)?a:9;'
+
+try 5 '{a=1; b=4; a+b;}'
+try 10 'a=2; switch (a) { case 1: b=5; break; case 2: b=10; break; default: b=20; } b;'
+try 20 'a=3; switch (a) { case 1: b=5; break; case 2: b=10; break; default: b=20; } b;'
+try 7 'b=7; switch (3) { case 1: b=5; } b;'
+try 11 'a=1; b=0; switch (a) { case 1: b=b+1; case 2: b=b+10; break; case 3: b=b+100; } b;'
+try 6 'a=0; switch (1) { case 1: switch (2) { case 2: a=5; break; } a=a+1; } a;'
+
+try 3 'a=1; goto skip; a=2; skip: a+2;'
+try 10 'a=0; top: a=a+1; switch (a) { case 10: goto done; } goto top; done: a;'
+try 4 'a=0; goto b; c: a=a+1; goto d; b: a=3; goto c; d: a;'
+
+try 97 "'a';"
+try 10 "'\\n';"
+try 65 "'\\x41';"
+try 1 "'b'-'a';"
+
+try 3 'foo=1; far=2; foo+far;'
+try 5 'Foo_1=2; _bar=3; Foo_1+_bar;'
+try 6 $'#define N 3\nN+N;'
+try 4 $'#define TWO 2 // two\n#define FOUR TWO*TWO\nFOUR;'
+try 1 $'#define N 1\n#undef N\nN=1; N;'
+echo '#define ANSWER 42' > "${TARGET}/answer.h"
+try 42 $'#include "answer.h"\nANSWER;'
+try 3 $'#define ADD(a, b) ((a)+(b))\nADD(1, 2);'
+try 9 $'#define SQ(x) ((x)*(x))\nSQ(1+2);'
+try 12 $'#define CAT(a, b) a ## b\nCAT(1, 2);'
+try 3 $'#define SUM(a, ...) a __VA_OPT__(+ __VA_ARGS__)\nSUM(1, 2);'
+try 1 $'#define SUM(a, ...) a __VA_OPT__(+ __VA_ARGS__)\nSUM(1);'
+try 2 $'#define N 2\n#if N > 1 && !defined(M)\nN;\n#else\n0;\n#endif'
+try 3 $'#ifdef M\n1;\n#elif 1 << 2 == 4\n3;\n#endif'
+try 3 $'\n\n__LINE__;'
+try 41 $'#line 40\n__LINE__ + __STDC__;'
+try 1 $'__COUNTER__;\n__COUNTER__;'
+
+# constants which do not fit in 32 bits.
+try 1 '4294967296 + 1;'
+try 255 "'\\xff\\xff\\xff\\xff' - 4294967040;"
+try 1 "L'\\xffffffff' == 4294967295;"
+try 1 '18446744073709551615 + 2;'
+
+check() {
+  expected="$1"
+  name="$2"
+
+  "${TARGET}/tmp"
+  actual="$?"
+  if [ "$actual" != "$expected" ]; then
+    echo "$name => $expected expected, but got $actual"
+    exit 1
+  fi
+  echo "$name => $actual"
+}
+
+# "-" reads the program from stdin.
+echo '6*7;' | ${CMD} -o "${TARGET}/tmp" -
+check 42 stdin
+
+# -S and -c stop after assembly and object code, -E after preprocessing.
+echo 'N*2;' > "${TARGET}/stage.c"
+${CMD} -S -DN=5 -o "${TARGET}/stage.s" "${TARGET}/stage.c" && gcc -o "${TARGET}/tmp" "${TARGET}/stage.s"
+check 10 -S
+${CMD} -c -DN=6 -o "${TARGET}/stage.o" "${TARGET}/stage.c" && gcc -o "${TARGET}/tmp" "${TARGET}/stage.o"
+check 12 -c
+${CMD} -E -DN=7 -o "${TARGET}/stage.i" "${TARGET}/stage.c" && ${CMD} -o "${TARGET}/tmp" "${TARGET}/stage.i"
+check 14 -E
+mkdir -p "${TARGET}/inc"
+echo '#define N 8' > "${TARGET}/inc/n.h"
+printf '#include <n.h>\nN;' | ${CMD} -I "${TARGET}/inc" -o "${TARGET}/tmp" -
+check 8 -I
+# each unit is translated on its own, other inputs go to the assembler or the linker.
+mkdir -p "${TARGET}/units"
+echo '40+2;' > "${TARGET}/units/a.c"
+echo '1+;' > "${TARGET}/units/bad.c"
+printf '.text\n.global helper\nhelper:\n  ret\n' > "${TARGET}/units/helper.s"
+(cd "${TARGET}/units" && ../../../${CMD} -c a.c helper.s) || exit 1
+${CMD} -o "${TARGET}/tmp" "${TARGET}/units/a.o" "${TARGET}/units/helper.o"
+check 42 units
+${CMD} -o "${TARGET}/tmp" "${TARGET}/units/a.c" "${TARGET}/units/helper.s"
+check 42 units
+rm -f "${TARGET}/units/a.o"
+if (cd "${TARGET}/units" && ../../../${CMD} -c bad.c a.c 2> /dev/null); then
+  echo "bad.c => failure expected"
+  exit 1
+fi
+if [ ! -f "${TARGET}/units/a.o" ]; then
+  echo "a.c => a.o expected after bad.c failed"
+  exit 1
+fi
+
+# --emit dumps the tokens at their place in the source, and the tree with variable names.
+printf '#define N 2\na = N;\n' > "${TARGET}/emit.c"
+expected=$(printf '%s\n' "${TARGET}/emit.c:2:1: a" "${TARGET}/emit.c:2:3: =" \
+  "${TARGET}/emit.c:2:5: 2 from N" "${TARGET}/emit.c:2:6: ;" "${TARGET}/emit.c:3:1: end of input")
+actual=$(${CMD} --emit=tokens -o - "${TARGET}/emit.c")
+if [ "$actual" != "$expected" ]; then
+  echo "--emit=tokens => '$expected' expected, but got '$actual'"
+  exit 1
+fi
+actual=$(${CMD} --emit=ast -o - "${TARGET}/emit.c" | grep LocalVar)
+if [ "$actual" != "  LocalVar a [rbp-8]" ]; then
+  echo "--emit=ast => '  LocalVar a [rbp-8]' expected, but got '$actual'"
+  exit 1
+fi
+echo "--emit => OK"
+
+# every lexical or syntax error of a unit is reported, up to -fmax-errors.
+printf '1 @ 2;\n$;\n`;\n' > "${TARGET}/lex_errors.c"
+printf '1 + ;\n2 3;\n{ (4; }\n' > "${TARGET}/syntax_errors.c"
+for errors in lex_errors syntax_errors; do
+  for limit in 0 2; do
+    expected=$([ "$limit" = 0 ] && echo 3 || echo "$limit")
+    actual=$(${CMD} -fmax-errors=$limit -S -o /dev/null "${TARGET}/$errors.c" 2>&1 | grep -c ': error: ')
+    if [ "$actual" != "$expected" ]; then
+      echo "$errors -fmax-errors=$limit => $expected errors expected, but got $actual"
+      exit 1
+    fi
+    echo "$errors -fmax-errors=$limit => $actual errors"
+  done
+done
+
+# warnings other than #warning are off unless turned on, and fail the unit with -Werror.
+printf '#warning check\na = b;\nunused: a == a;\n' > "${TARGET}/warnings.c"
+for flags in "" "-Wall" "-Wall -Wno-uninitialized" "-Wall -Werror"; do
+  output=$(${CMD} ${flags} -S -o /dev/null "${TARGET}/warnings.c" 2>&1)
+  status=$?
+  actual="$(echo "$output" | grep -c ': warning: ') $(echo "$output" | grep -c ': error: ') $status"
+  case "$flags" in
+    "") expected="1 0 0" ;;
+    "-Wall") expected="4 0 0" ;;
+    "-Wall -Wno-uninitialized") expected="3 0 0" ;;
+    *) expected="0 4 1" ;;
+  esac
+  if [ "$actual" != "$expected" ]; then
+    echo "warnings.c ${flags} => '$expected' expected, but got '$actual'"
+    exit 1
+  fi
+  echo "warnings.c ${flags} => $actual"
+done
+
 echo OK